@@ -0,0 +1,58 @@
+use std::path::PathBuf;
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Embeds the git commit and build time for the `/version` endpoint.
+fn main() {
+    let commit = std::env::var("GIT_COMMIT").ok().or_else(|| {
+        Command::new("git")
+            .args(["rev-parse", "HEAD"])
+            .output()
+            .ok()
+            .filter(|output| output.status.success())
+            .and_then(|output| String::from_utf8(output.stdout).ok())
+            .map(|commit| commit.trim().to_string())
+    });
+    println!(
+        "cargo:rustc-env=GIT_COMMIT={}",
+        commit.as_deref().unwrap_or("unknown")
+    );
+    let build_time = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+    println!("cargo:rustc-env=BUILD_TIMESTAMP={}", build_time);
+    println!("cargo:rerun-if-env-changed=GIT_COMMIT");
+    rerun_on_commit();
+}
+
+/// Reruns the script when HEAD moves, by switching branches or committing on the current one.
+fn rerun_on_commit() {
+    let Some(git_dir) = Command::new("git")
+        .args(["rev-parse", "--git-dir"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| String::from_utf8(output.stdout).ok())
+        .map(|dir| PathBuf::from(dir.trim()))
+    else {
+        return;
+    };
+    let head = git_dir.join("HEAD");
+    let mut watched = vec![head.clone(), git_dir.join("packed-refs")];
+    if let Some(reference) = std::fs::read_to_string(&head)
+        .ok()
+        .and_then(|head| Some(head.strip_prefix("ref:")?.trim().to_string()))
+    {
+        let reference = git_dir.join(reference);
+        // A packed ref gets its own file again on the next commit
+        match reference.parent().filter(|_| !reference.exists()) {
+            Some(dir) => watched.push(dir.to_path_buf()),
+            None => watched.push(reference),
+        }
+    }
+    // A path that does not exist would rerun the script on every build
+    for path in watched.into_iter().filter(|path| path.exists()) {
+        println!("cargo:rerun-if-changed={}", path.display());
+    }
+}
@@ -0,0 +1,439 @@
+use crate::caching::{etag_matches, CACHE_CONTROL};
+use crate::collections::{
+    ssr::{read_entries, read_entry},
+    tag_slug, Collection, Entry,
+};
+use crate::error_template::AppError;
+use crate::lifecycle::Status;
+use crate::SITE_URL;
+use axum::{
+    extract::{Path, Query},
+    http::{header, HeaderMap, HeaderValue, Method, StatusCode},
+    response::{IntoResponse, Response},
+    routing::get,
+    Json, Router,
+};
+use chrono::Datelike;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use tower_http::cors::{AllowOrigin, CorsLayer};
+
+/// Comma separated origins allowed to call the API from a browser, any origin when unset.
+const CORS_ORIGINS_ENV: &str = "API_CORS_ORIGINS";
+const DEFAULT_PER_PAGE: usize = 20;
+const MAX_PER_PAGE: usize = 100;
+
+/// The read-only project API, nested at `/api/v1`.
+pub fn router<S: Clone + Send + Sync + 'static>() -> Router<S> {
+    Router::new()
+        .route("/projects", get(projects))
+        .route("/projects/{slug}", get(project))
+        .route("/tags", get(tags))
+        .route("/openapi.json", get(openapi))
+        .layer(cors())
+}
+
+fn cors() -> CorsLayer {
+    let origins = std::env::var(CORS_ORIGINS_ENV)
+        .ok()
+        .map(|origins| {
+            origins
+                .split(',')
+                .filter_map(|origin| HeaderValue::from_str(origin.trim()).ok())
+                .collect::<Vec<_>>()
+        })
+        .map(AllowOrigin::list)
+        .unwrap_or_else(AllowOrigin::any);
+    CorsLayer::new()
+        .allow_origin(origins)
+        .allow_methods([Method::GET, Method::HEAD])
+        .allow_headers([header::IF_NONE_MATCH])
+        .expose_headers([header::ETAG])
+}
+
+fn slug(entry: &Entry) -> &str {
+    entry.url.rsplit('/').next().unwrap_or_default()
+}
+
+fn project_json(entry: &Entry) -> Value {
+    json!({
+        "slug": slug(entry),
+        "title": entry.name,
+        "url": format!("{}{}", SITE_URL, entry.url),
+        "date": entry.date,
+        "description": entry.description,
+        "tags": entry.tags,
+        "start": entry.lifecycle.start,
+        "end": entry.lifecycle.end,
+        "status": entry.lifecycle.status,
+    })
+}
+
+/// Serializes `value` with an ETag, answering `304 Not Modified` if the client has it.
+fn cached_json(headers: &HeaderMap, value: Value) -> Response {
+    let body = value.to_string();
+    let etag = format!("\"{:x}\"", Sha256::digest(&body));
+    let cache_headers = [
+        (header::ETAG, etag.clone()),
+        (header::CACHE_CONTROL, CACHE_CONTROL.to_string()),
+    ];
+    if etag_matches(headers, &etag) {
+        return (StatusCode::NOT_MODIFIED, cache_headers).into_response();
+    }
+    (
+        cache_headers,
+        [(header::CONTENT_TYPE, "application/json".to_string())],
+        body,
+    )
+        .into_response()
+}
+
+/// Answers with the error, server errors are logged and only described generically.
+fn error(error: AppError) -> Response {
+    let status = error.status_code();
+    let message = if status.is_server_error() {
+        tracing::error!("api request failed: {}", error);
+        status.canonical_reason().unwrap_or("error").to_lowercase()
+    } else {
+        error.to_string()
+    };
+    (status, Json(json!({ "error": message }))).into_response()
+}
+
+#[derive(Deserialize)]
+struct ProjectsQuery {
+    tag: Option<String>,
+    year: Option<i32>,
+    status: Option<Status>,
+    page: Option<usize>,
+    per_page: Option<usize>,
+}
+
+/// The projects newest first, filtered by tag, year and status and paginated.
+async fn projects(headers: HeaderMap, Query(query): Query<ProjectsQuery>) -> Response {
+    let page = query.page.unwrap_or(1);
+    let per_page = query.per_page.unwrap_or(DEFAULT_PER_PAGE);
+    let skip = page.checked_sub(1).and_then(|p| p.checked_mul(per_page));
+    let Some(skip) = skip.filter(|_| per_page != 0 && per_page <= MAX_PER_PAGE) else {
+        return error(AppError::BadRequest(format!(
+            "page must be at least 1 and per_page between 1 and {}",
+            MAX_PER_PAGE
+        )));
+    };
+    let tag = query.tag.as_deref().map(tag_slug);
+    let projects = read_entries(Collection::Projects)
+        .await
+        .into_iter()
+        .filter(|p| match &tag {
+            Some(tag) => p.tags.iter().any(|t| &tag_slug(t) == tag),
+            None => true,
+        })
+        .filter(|p| query.year.is_none_or(|year| p.date.year() == year))
+        .filter(|p| query.status.is_none_or(|status| p.lifecycle.status == Some(status)))
+        .collect::<Vec<_>>();
+    let items = projects
+        .iter()
+        .skip(skip)
+        .take(per_page)
+        .map(project_json)
+        .collect::<Vec<_>>();
+    cached_json(
+        &headers,
+        json!({
+            "items": items,
+            "page": page,
+            "per_page": per_page,
+            "total": projects.len(),
+        }),
+    )
+}
+
+/// A single project with its rendered html and markdown source.
+async fn project(headers: HeaderMap, Path(slug): Path<String>) -> Response {
+    if slug.contains('/') || slug.starts_with('.') {
+        return error(AppError::NotFound);
+    }
+    let (entry, markdown) = match read_entry(Collection::Projects, &slug).await {
+        Ok(entry) => entry,
+        Err(e) => return error(e),
+    };
+    let html = crate::images::rewrite_images(
+        &markdown::to_html(&markdown),
+        std::path::Path::new(Collection::Projects.config().dir),
+    )
+    .await;
+    let mut project = project_json(&entry);
+    project["html"] = html.into();
+    project["markdown"] = markdown.into();
+    cached_json(&headers, project)
+}
+
+/// Every project tag with the number of projects using it.
+async fn tags(headers: HeaderMap) -> Response {
+    let mut tags = BTreeMap::<String, (String, usize)>::new();
+    for project in read_entries(Collection::Projects).await {
+        for tag in project.tags {
+            tags.entry(tag_slug(&tag)).or_insert((tag, 0)).1 += 1;
+        }
+    }
+    let tags = tags
+        .into_iter()
+        .map(|(slug, (name, count))| json!({ "slug": slug, "name": name, "count": count }))
+        .collect::<Vec<_>>();
+    cached_json(&headers, json!(tags))
+}
+
+async fn openapi(headers: HeaderMap) -> Response {
+    let statuses = Status::ALL.map(Status::as_str);
+    let project = json!({
+        "type": "object",
+        "required": ["slug", "title", "url", "date", "description", "tags"],
+        "properties": {
+            "slug": { "type": "string" },
+            "title": { "type": "string" },
+            "url": { "type": "string", "format": "uri" },
+            "date": { "type": "string", "format": "date" },
+            "description": { "type": "string" },
+            "tags": { "type": "array", "items": { "type": "string" } },
+            "start": { "type": ["string", "null"], "description": "When work started, as YYYY, YYYY-MM or YYYY-MM-DD" },
+            "end": { "type": ["string", "null"], "description": "When work ended, as YYYY, YYYY-MM or YYYY-MM-DD" },
+            "status": { "oneOf": [{ "enum": statuses }, { "type": "null" }] },
+        },
+    });
+    let error = json!({
+        "description": "Error",
+        "content": { "application/json": { "schema": { "$ref": "#/components/schemas/Error" } } },
+    });
+    let document = json!({
+        "openapi": "3.1.0",
+        "info": {
+            "title": "lukashermansson.me projects",
+            "version": "1",
+            "description": "Read-only access to the projects. Responses carry an ETag and answer 304 to a matching If-None-Match.",
+        },
+        "servers": [{ "url": format!("{}/api/v1", SITE_URL) }],
+        "paths": {
+            "/projects": {
+                "get": {
+                    "summary": "List the projects, newest first",
+                    "parameters": [
+                        { "name": "tag", "in": "query", "schema": { "type": "string" }, "description": "Only projects with this tag, matched by slug" },
+                        { "name": "year", "in": "query", "schema": { "type": "integer" } },
+                        { "name": "status", "in": "query", "schema": { "enum": statuses } },
+                        { "name": "page", "in": "query", "schema": { "type": "integer", "minimum": 1, "default": 1 } },
+                        { "name": "per_page", "in": "query", "schema": { "type": "integer", "minimum": 1, "maximum": MAX_PER_PAGE, "default": DEFAULT_PER_PAGE } },
+                    ],
+                    "responses": {
+                        "200": {
+                            "description": "A page of projects",
+                            "content": { "application/json": { "schema": {
+                                "type": "object",
+                                "required": ["items", "page", "per_page", "total"],
+                                "properties": {
+                                    "items": { "type": "array", "items": { "$ref": "#/components/schemas/Project" } },
+                                    "page": { "type": "integer" },
+                                    "per_page": { "type": "integer" },
+                                    "total": { "type": "integer" },
+                                },
+                            } } },
+                        },
+                        "304": { "description": "Not modified" },
+                        "400": error,
+                    },
+                },
+            },
+            "/projects/{slug}": {
+                "get": {
+                    "summary": "A project with its content",
+                    "parameters": [
+                        { "name": "slug", "in": "path", "required": true, "schema": { "type": "string" } },
+                    ],
+                    "responses": {
+                        "200": {
+                            "description": "The project",
+                            "content": { "application/json": { "schema": { "allOf": [
+                                { "$ref": "#/components/schemas/Project" },
+                                {
+                                    "type": "object",
+                                    "required": ["html", "markdown"],
+                                    "properties": {
+                                        "html": { "type": "string", "description": "The rendered content" },
+                                        "markdown": { "type": "string", "description": "The content without front matter" },
+                                    },
+                                },
+                            ] } } },
+                        },
+                        "304": { "description": "Not modified" },
+                        "404": error,
+                        "500": error,
+                    },
+                },
+            },
+            "/tags": {
+                "get": {
+                    "summary": "The project tags with their number of projects",
+                    "responses": {
+                        "200": {
+                            "description": "The tags",
+                            "content": { "application/json": { "schema": {
+                                "type": "array",
+                                "items": {
+                                    "type": "object",
+                                    "required": ["slug", "name", "count"],
+                                    "properties": {
+                                        "slug": { "type": "string" },
+                                        "name": { "type": "string" },
+                                        "count": { "type": "integer" },
+                                    },
+                                },
+                            } } },
+                        },
+                        "304": { "description": "Not modified" },
+                    },
+                },
+            },
+        },
+        "components": {
+            "schemas": {
+                "Project": project,
+                "Error": {
+                    "type": "object",
+                    "required": ["error"],
+                    "properties": { "error": { "type": "string" } },
+                },
+            },
+        },
+    });
+    cached_json(&headers, document)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use axum::http::Request;
+    use tower::ServiceExt;
+
+    fn get(uri: &str, if_none_match: Option<&str>) -> (StatusCode, HeaderMap, Value) {
+        let mut request = Request::get(uri);
+        if let Some(etag) = if_none_match {
+            request = request.header(header::IF_NONE_MATCH, etag);
+        }
+        tokio::runtime::Runtime::new().unwrap().block_on(async {
+            let response = router::<()>()
+                .oneshot(request.body(Body::empty()).unwrap())
+                .await
+                .unwrap();
+            let (parts, body) = response.into_parts();
+            let body = axum::body::to_bytes(body, usize::MAX).await.unwrap();
+            let value = serde_json::from_slice(&body).unwrap_or(Value::Null);
+            (parts.status, parts.headers, value)
+        })
+    }
+
+    fn slugs(value: &Value) -> Vec<&str> {
+        value["items"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|item| item["slug"].as_str().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn lists_projects_newest_first() {
+        let (status, _, value) = get("/projects", None);
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(value["page"], 1);
+        assert_eq!(value["per_page"], DEFAULT_PER_PAGE);
+        let items = value["items"].as_array().unwrap();
+        assert!(!items.is_empty());
+        assert!(items
+            .windows(2)
+            .all(|w| w[0]["date"].as_str() >= w[1]["date"].as_str()));
+    }
+
+    #[test]
+    fn filters_projects() {
+        let (_, _, value) = get("/projects?tag=open-source&year=2023&per_page=100", None);
+        assert!(slugs(&value).contains(&"kontrolluppgift"));
+        for item in value["items"].as_array().unwrap() {
+            assert!(item["tags"]
+                .as_array()
+                .unwrap()
+                .contains(&json!("Open source")));
+            assert!(item["date"].as_str().unwrap().starts_with("2023-"));
+        }
+        let (_, _, value) = get("/projects?status=superseded&per_page=100", None);
+        assert!(slugs(&value).contains(&"website"));
+        assert!(value["items"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .all(|item| item["status"] == "superseded"));
+        let (_, _, value) = get("/projects?year=1999", None);
+        assert_eq!(value["total"], 0);
+        let (status, _, _) = get("/projects?status=unknown", None);
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[test]
+    fn paginates_projects() {
+        let (_, _, value) = get(&format!("/projects?per_page={}", MAX_PER_PAGE), None);
+        let all = slugs(&value);
+        assert_eq!(value["total"], all.len());
+        let (_, _, value) = get("/projects?page=2&per_page=2", None);
+        assert_eq!(slugs(&value), all[2..4]);
+        assert_eq!(value["total"], all.len());
+        let (status, _, value) = get(&format!("/projects?page={}", all.len() + 1), None);
+        assert_eq!(status, StatusCode::OK);
+        assert!(slugs(&value).is_empty());
+    }
+
+    #[test]
+    fn rejects_pages_out_of_bounds() {
+        for query in [
+            "page=0".to_string(),
+            "per_page=0".to_string(),
+            format!("per_page={}", MAX_PER_PAGE + 1),
+            format!("page={}", usize::MAX),
+            format!("page={}&per_page={}", usize::MAX / 2, MAX_PER_PAGE),
+            "page=-1".to_string(),
+        ] {
+            let (status, _, _) = get(&format!("/projects?{}", query), None);
+            assert_eq!(status, StatusCode::BAD_REQUEST, "{}", query);
+        }
+    }
+
+    #[test]
+    fn answers_not_modified_to_a_matching_etag() {
+        let (status, headers, _) = get("/tags", None);
+        assert_eq!(status, StatusCode::OK);
+        let etag = headers[header::ETAG].to_str().unwrap();
+        let (status, headers, _) = get("/tags", Some(etag));
+        assert_eq!(status, StatusCode::NOT_MODIFIED);
+        assert_eq!(headers[header::ETAG], etag);
+        let (status, _, _) = get("/tags", Some(&format!("\"other\", {}", etag)));
+        assert_eq!(status, StatusCode::NOT_MODIFIED);
+        let (status, _, _) = get("/tags", Some("\"other\""));
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    #[test]
+    fn hides_the_details_of_server_errors() {
+        let response = error(AppError::Internal(
+            "reading /app/projects/a.mdx".to_string(),
+        ));
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        let body = tokio::runtime::Runtime::new()
+            .unwrap()
+            .block_on(axum::body::to_bytes(response.into_body(), usize::MAX))
+            .unwrap();
+        assert_eq!(body, r#"{"error":"internal server error"}"#);
+        let (status, _, value) = get("/projects/missing", None);
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(value["error"], "not found");
+    }
+}
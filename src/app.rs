@@ -1,6 +1,15 @@
+use crate::blog::Blog;
+use crate::blog::Post;
+use crate::collections::Collection;
+use crate::collections::Listing;
+use crate::error_template::{AppError, ErrorPage};
+use crate::history::History;
 use crate::navbar::Navbar;
+use crate::pages::MarkdownPage;
 use crate::projects::Project;
 use crate::projects::Projects;
+use crate::theme::current_theme;
+use crate::theme::ThemeColor;
 use leptos::prelude::*;
 use leptos_meta::provide_meta_context;
 use leptos_meta::Meta;
@@ -13,20 +22,43 @@ use leptos_router::components::RoutingProgress;
 use leptos_router::*;
 
 pub fn shell(options: LeptosOptions) -> impl IntoView {
+    // Inline scripts get the nonce the Content-Security-Policy allows
+    #[cfg(feature = "ssr")]
+    crate::security::provide_nonce();
+    #[cfg(feature = "ssr")]
+    crate::locale::ssr::provide_locale();
+    let theme = current_theme();
+    let stylesheet = crate::bundle::Bundle::get(&options).css.clone();
+    // With the critical css inlined the full stylesheet no longer blocks the first paint
+    #[cfg(feature = "ssr")]
+    let (critical, nonce) = (crate::critical_css::current(), leptos::nonce::use_nonce());
+    #[cfg(not(feature = "ssr"))]
+    let (critical, nonce) = (None::<std::sync::Arc<str>>, None::<String>);
+    let deferred = critical.is_some();
     view! {
         <!DOCTYPE html>
-        <html lang="en">
+        <html lang="en" data-theme=theme.as_str()>
             <head>
                 <meta charset="utf-8"/>
                 <meta name="viewport" content="width=device-width, initial-scale=1"/>
-                <link rel="manifest" href="manifest.json" />
+                <meta name="color-scheme" content="light dark"/>
+                <ThemeColor theme/>
+                // Credentials so the theme cookie is sent and the manifest colors match
+                <link rel="manifest" href="/manifest.json" crossorigin="use-credentials" />
                 <AutoReload options=options.clone() />
-                <HydrationScripts options/>
-                <link rel="stylesheet" id="leptos" href="/pkg/website-2.css"/>
+                <HydrationScripts options islands=true/>
+                {match critical {
+                    Some(css) => {
+                        view! { <style nonce=nonce inner_html=css.to_string()></style> }
+                            .into_any()
+                    }
+                    None => view! { <link rel="stylesheet" id="leptos" href=stylesheet.clone()/> }.into_any(),
+                }}
                 <MetaTags/>
             </head>
-            <body class="bg-gray-900">
+            <body class="bg-gray-50 dark:bg-gray-900">
                 <App/>
+                {deferred.then(|| view! { <link rel="stylesheet" id="leptos" href=stylesheet/> })}
             </body>
         </html>
     }
@@ -35,28 +67,54 @@ pub fn shell(options: LeptosOptions) -> impl IntoView {
 pub fn App() -> impl IntoView {
     // Provides context that manages stylesheets, titles, meta tags, etc.
     provide_meta_context();
-    let (is_routing, set_is_routing) = signal(false);
 
     view! {
         <Meta name="description" content="Lukas Hermansson's personal website"/>
         // sets the document title
         <Title text="Lukas Hermansson"/>
 
-        <Router
-            set_is_routing
-        >
+        <Router>
             <div class="routing-progress">
-                <RoutingProgress is_routing max_time=std::time::Duration::from_millis(250) {..}
-
-            />
+                <NavigationProgress/>
             </div>
             <div></div>
             <Navbar/>
             <main>
-                <FlatRoutes fallback=|| "Not found.">
+                <FlatRoutes fallback=|| view! { <ErrorPage error=AppError::NotFound/> }>
                     <Route path=StaticSegment("") view=HomePage/>
                     <Route path=StaticSegment("projects") view=Projects/>
-                    <Route path=(StaticSegment("projects"), ParamSegment("id")) view=Project/>
+                    <Route
+                        path=(StaticSegment("projects"), ParamSegment("id"))
+                        view=Project
+                        ssr=SsrMode::Async
+                    />
+                    <Route
+                        path=(StaticSegment("projects"), ParamSegment("id"), StaticSegment("history"))
+                        view=|| view! { <History collection=Collection::Projects/> }
+                        ssr=SsrMode::Async
+                    />
+                    <Route
+                        path=(StaticSegment("projects"), StaticSegment("tags"), ParamSegment("tag"))
+                        view=|| view! { <Listing collection=Collection::Projects/> }
+                    />
+                    <Route path=StaticSegment("blog") view=Blog/>
+                    <Route
+                        path=(StaticSegment("blog"), ParamSegment("id"))
+                        view=Post
+                        ssr=SsrMode::Async
+                    />
+                    <Route
+                        path=(StaticSegment("blog"), ParamSegment("id"), StaticSegment("history"))
+                        view=|| view! { <History collection=Collection::Blog/> }
+                        ssr=SsrMode::Async
+                    />
+                    <Route
+                        path=(StaticSegment("blog"), StaticSegment("tags"), ParamSegment("tag"))
+                        view=|| view! { <Listing collection=Collection::Blog/> }
+                    />
+                    <Route path=StaticSegment("offline") view=Offline/>
+                    // Anything else is looked up in the `pages` directory
+                    <Route path=WildcardSegment("path") view=MarkdownPage ssr=SsrMode::Async/>
                 </FlatRoutes>
             </main>
 
@@ -65,15 +123,31 @@ pub fn App() -> impl IntoView {
     }
 }
 
+/// Shows progress from when a navigation starts until the next page has loaded.
+///
+/// Navigations are full page loads as only the islands are hydrated.
+#[island]
+fn NavigationProgress() -> impl IntoView {
+    let (is_routing, set_is_routing) = signal(false);
+    Effect::new(move |_| {
+        let _ = window_event_listener(leptos::ev::beforeunload, move |_| {
+            set_is_routing.set(true)
+        });
+        // Pages restored from the back/forward cache are already loaded
+        let _ = window_event_listener(leptos::ev::pageshow, move |_| set_is_routing.set(false));
+    });
+    view! { <RoutingProgress is_routing max_time=std::time::Duration::from_millis(250)/> }
+}
+
 #[component]
 fn HomePage() -> impl IntoView {
     view! {
         <Meta property="og:title" content="Lukas Hermansson"/>
         <Meta property="og:description" content="Lukas Hermansson's personal website"/>
         <Meta property="og:type" content="website"/>
-        <Meta property="og:image" content="https://www.lukashermansson.me/assets/og-card.jpg"/>
+        <Meta property="og:image" content=format!("{}/assets/og-card.jpg", crate::SITE_URL)/>
         <Title text="Lukas Hermansson"/>
-        <div class="m-auto md:w-3/5 w-full flex flex-col text-gray-400 ">
+        <div class="m-auto md:w-3/5 w-full flex flex-col text-gray-700 dark:text-gray-400 ">
             <h2 class="font-bold text-3xl text-center m-6">My code-values</h2>
             <div class="text-center">
                 <p class="m-2">
@@ -103,10 +177,22 @@ fn HomePage() -> impl IntoView {
         </div>
     }
 }
+/// Served by the service worker when a page is requested offline and isn't cached.
+#[component]
+fn Offline() -> impl IntoView {
+    view! {
+        <Title text="Lukas Hermansson - Offline"/>
+        <div class="m-auto md:w-3/5 w-full max-md:m-2 flex flex-col items-center text-gray-700 dark:text-gray-400">
+            <h1 class="text-4xl my-3 font-bold">"You are offline"</h1>
+            <p class="m-2">"This page has not been saved for offline use, try again when you are back online."</p>
+            <a href="/" class="underline m-2">"Go to the home page"</a>
+        </div>
+    }
+}
 #[component]
 fn ProgrammingLang(language_name: &'static str, url: &'static str) -> impl IntoView {
     view! {
-        <div class="p-3 flex flex-col items-center rounded shadow-md shadow-gray-950 bg-slate-800 min-w-[190px]">
+        <div class="p-3 flex flex-col items-center rounded shadow-md shadow-gray-300 dark:shadow-gray-950 bg-white dark:bg-slate-800 min-w-[190px]">
             <img alt=language_name src=url class="h-12"/>
             <p>{language_name}</p>
         </div>
@@ -115,14 +201,14 @@ fn ProgrammingLang(language_name: &'static str, url: &'static str) -> impl IntoV
 #[component]
 fn Footer() -> impl IntoView {
     view! {
-        <hr class="border-dashed border-0 border-b-2 w-full border-gray-500 mt-5"/>
-        <footer class="flex p-1 items-center flex-col justify-center text-gray-400 mt-7">
+        <hr class="border-dashed border-0 border-b-2 w-full border-gray-400 dark:border-gray-500 mt-5"/>
+        <footer class="flex p-1 items-center flex-col justify-center text-gray-700 dark:text-gray-400 mt-7">
             <div class="text-center">
-                <hr class="border-dashed border-0 border-b-2 w-full border-gray-500"/>
+                <hr class="border-dashed border-0 border-b-2 w-full border-gray-400 dark:border-gray-500"/>
                 "Copyright © 2023 Lukas Hermansson"
                 <br/>
                 Made with Leptos and Rust
-                <hr class="border-dashed border-0 border-b-2 w-full border-gray-500"/>
+                <hr class="border-dashed border-0 border-b-2 w-full border-gray-400 dark:border-gray-500"/>
             </div>
             <div class="flex gap-2 my-4">
                 <a href="https://github.com/lukashermansson" aria-label="github">
@@ -0,0 +1,55 @@
+use crate::collections::{Collection, CollectionConfig, Detail, Listing, SortOrder};
+use leptos::prelude::*;
+
+pub const CONFIG: CollectionConfig = CollectionConfig {
+    dir: "./blog",
+    extension: "md",
+    route_prefix: "blog",
+    title: "Blog",
+    item_label: "post",
+    description: "Lukas Hermansson's blog",
+    sort: SortOrder::NewestFirst,
+};
+
+#[cfg(feature = "ssr")]
+pub mod ssr {
+    use crate::collections::{ssr::FrontMatter, Entry};
+    use chrono::NaiveDate;
+    use serde::Deserialize;
+
+    #[derive(Deserialize)]
+    pub struct PostFrontMatter {
+        title: String,
+        date: NaiveDate,
+        description: String,
+        #[serde(default)]
+        tags: Vec<String>,
+        updated: Option<NaiveDate>,
+    }
+
+    impl FrontMatter for PostFrontMatter {
+        fn into_entry(self, url: String, words: usize) -> Entry {
+            Entry {
+                url,
+                name: self.title,
+                date: self.date,
+                description: self.description,
+                tags: self.tags,
+                updated: self.updated,
+                published: None,
+                lifecycle: Default::default(),
+                words,
+            }
+        }
+    }
+}
+
+#[component]
+pub fn Blog() -> impl IntoView {
+    view! { <Listing collection=Collection::Blog/> }
+}
+
+#[component]
+pub fn Post() -> impl IntoView {
+    view! { <Detail collection=Collection::Blog/> }
+}
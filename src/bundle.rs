@@ -0,0 +1,98 @@
+use leptos::config::LeptosOptions;
+use std::sync::OnceLock;
+
+/// Urls of the stylesheet and the JS/WASM bundle built by cargo-leptos, named the way
+/// `HydrationScripts` names them.
+pub struct Bundle {
+    pub css: String,
+    pub js: String,
+    pub wasm: String,
+}
+
+impl Bundle {
+    /// The bundle of this build, the hash file is only read once.
+    pub fn get(options: &LeptosOptions) -> &'static Bundle {
+        static BUNDLE: OnceLock<Bundle> = OnceLock::new();
+        BUNDLE.get_or_init(|| Bundle::new(options))
+    }
+
+    fn new(options: &LeptosOptions) -> Bundle {
+        let name = options.output_name.as_ref();
+        let (mut css, mut js, mut wasm) = (name.to_string(), name.to_string(), name.to_string());
+        if options.hash_files {
+            // cargo-leptos writes `<kind>: <hash>` lines next to the server binary
+            let hashes = std::env::current_exe()
+                .ok()
+                .and_then(|exe| {
+                    std::fs::read_to_string(exe.with_file_name(options.hash_file.as_ref())).ok()
+                })
+                .unwrap_or_default();
+            for (kind, hash) in hashes.lines().filter_map(|line| line.split_once(':')) {
+                match kind.trim() {
+                    "css" => css = format!("{}.{}", name, hash.trim()),
+                    "js" => js = format!("{}.{}", name, hash.trim()),
+                    "wasm" => wasm = format!("{}.{}", name, hash.trim()),
+                    _ => {}
+                }
+            }
+        } else if option_env!("LEPTOS_OUTPUT_NAME").is_none() {
+            wasm.push_str("_bg");
+        }
+        let pkg = options.site_pkg_dir.as_ref();
+        Bundle {
+            css: format!("/{}/{}.css", pkg, css),
+            js: format!("/{}/{}.js", pkg, js),
+            wasm: format!("/{}/{}.wasm", pkg, wasm),
+        }
+    }
+
+    /// The `Link` header preloading the bundle.
+    ///
+    /// The crossorigin attributes match the ones `HydrationScripts` puts on its links, so the
+    /// browser reuses the preloaded responses.
+    pub fn link_header(&self) -> String {
+        format!(
+            "<{}>; rel=preload; as=style, <{}>; rel=modulepreload; crossorigin, \
+             <{}>; rel=preload; as=fetch; type=\"application/wasm\"; crossorigin",
+            self.css, self.js, self.wasm
+        )
+    }
+}
+
+#[cfg(feature = "ssr")]
+pub mod ssr {
+    use super::Bundle;
+    use axum::{
+        extract::{Request, State},
+        http::{header, HeaderValue, Method},
+        middleware::Next,
+        response::Response,
+    };
+    use leptos::config::LeptosOptions;
+
+    /// Adds the preload `Link` header to html responses, it is sent before the streamed body so
+    /// the browser fetches the bundle while the page renders.
+    ///
+    /// Proxies supporting it turn the header into a `103 Early Hints` response.
+    pub async fn preload_links(
+        State(options): State<LeptosOptions>,
+        req: Request,
+        next: Next,
+    ) -> Response {
+        if req.method() != Method::GET {
+            return next.run(req).await;
+        }
+        let mut response = next.run(req).await;
+        let is_html = response
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|v| v.starts_with("text/html"));
+        if is_html {
+            if let Ok(link) = HeaderValue::from_str(&Bundle::get(&options).link_header()) {
+                response.headers_mut().append(header::LINK, link);
+            }
+        }
+        response
+    }
+}
@@ -0,0 +1,84 @@
+use axum::{
+    extract::Request,
+    http::{header, HeaderMap, HeaderValue, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use leptos::server_fn::ServerFn;
+use sha2::{Digest, Sha256};
+
+/// Lifetime of the API and server function responses, they are revalidated with their ETag
+/// afterwards.
+pub const CACHE_CONTROL: &str = "public, max-age=300";
+
+/// Whether `If-None-Match` lists `etag`.
+pub fn etag_matches(headers: &HeaderMap, etag: &str) -> bool {
+    headers
+        .get(header::IF_NONE_MATCH)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| {
+            v.split(',')
+                .map(str::trim)
+                .any(|tag| tag == etag || tag == "*")
+        })
+}
+
+/// An ETag for anything derived from the content, it changes with the content, the build and
+/// the HEAD the content dates and history are read at.
+pub async fn content_etag() -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(crate::health::content_hash().await);
+    hasher.update(crate::history::ssr::head().unwrap_or_default());
+    format!(
+        "\"{}-{}\"",
+        env!("GIT_COMMIT").get(..12).unwrap_or(env!("GIT_COMMIT")),
+        &format!("{:x}", hasher.finalize())[..16]
+    )
+}
+
+/// Makes the response of the server function `F` cacheable until the content changes.
+///
+/// Only when it is called through its endpoint, the pages rendering it carry a CSP nonce and
+/// the theme of the reader and must not be cached.
+pub async fn cache_server_fn<F: ServerFn>() {
+    let Some(response) = leptos::prelude::use_context::<leptos_axum::ResponseOptions>() else {
+        return;
+    };
+    let is_endpoint = leptos::prelude::use_context::<http::request::Parts>()
+        .is_some_and(|parts| parts.uri.path() == F::url());
+    if !is_endpoint {
+        return;
+    }
+    if let Ok(etag) = HeaderValue::from_str(&content_etag().await) {
+        response.insert_header(header::ETAG, etag);
+    }
+    response.insert_header(
+        header::CACHE_CONTROL,
+        HeaderValue::from_static(CACHE_CONTROL),
+    );
+}
+
+/// Answers `304 Not Modified` to a GET whose response has an ETag listed in `If-None-Match`.
+pub async fn not_modified(req: Request, next: Next) -> Response {
+    if req.method() != Method::GET || !req.headers().contains_key(header::IF_NONE_MATCH) {
+        return next.run(req).await;
+    }
+    let headers = req.headers().clone();
+    let response = next.run(req).await;
+    let etag = response
+        .headers()
+        .get(header::ETAG)
+        .and_then(|etag| etag.to_str().ok());
+    match etag {
+        Some(etag) if response.status() == StatusCode::OK && etag_matches(&headers, etag) => {
+            let mut not_modified = StatusCode::NOT_MODIFIED.into_response();
+            for name in [header::ETAG, header::CACHE_CONTROL, header::VARY] {
+                if let Some(value) = response.headers().get(&name) {
+                    not_modified.headers_mut().insert(name, value.clone());
+                }
+            }
+            not_modified
+        }
+        _ => response,
+    }
+}
@@ -0,0 +1,665 @@
+use crate::error_template::{error_template, AppError};
+use crate::history::LastUpdated;
+use crate::lifecycle::{Lifecycle, LifecycleLine, Status};
+use chrono::NaiveDate;
+use leptos::prelude::*;
+use leptos::server_fn::codec::{Cbor, GetUrl};
+use leptos_meta::{Link, Meta, Title};
+use leptos_router::components::A;
+use leptos_router::hooks::{use_params_map, use_query_map};
+use serde::{Deserialize, Serialize};
+
+/// A directory of markdown files with front matter, rendered as a listing and detail pages.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Collection {
+    Projects,
+    Blog,
+}
+
+#[derive(Clone, Copy)]
+pub enum SortOrder {
+    NewestFirst,
+    OldestFirst,
+}
+
+pub struct CollectionConfig {
+    /// Directory the entries are read from, relative to the working directory.
+    pub dir: &'static str,
+    pub extension: &'static str,
+    /// First path segment of the routes, `/{route_prefix}/{slug}`.
+    pub route_prefix: &'static str,
+    pub title: &'static str,
+    /// What a single entry is called, used in the page descriptions.
+    pub item_label: &'static str,
+    pub description: &'static str,
+    pub sort: SortOrder,
+}
+
+impl Collection {
+    pub const ALL: [Collection; 2] = [Collection::Projects, Collection::Blog];
+
+    pub fn config(self) -> &'static CollectionConfig {
+        match self {
+            Collection::Projects => &crate::projects::CONFIG,
+            Collection::Blog => &crate::blog::CONFIG,
+        }
+    }
+
+    pub fn tag_url(self, tag: &str) -> String {
+        format!("/{}/tags/{}", self.config().route_prefix, tag_slug(tag))
+    }
+
+    pub fn feed_url(self) -> String {
+        format!("/{}/feed.xml", self.config().route_prefix)
+    }
+
+    pub fn og_image_url(self, slug: &str) -> String {
+        match self {
+            Collection::Projects => format!("{}/og/{}.png", crate::SITE_URL, slug),
+            _ => format!(
+                "{}/og/{}/{}.png",
+                crate::SITE_URL,
+                self.config().route_prefix,
+                slug
+            ),
+        }
+    }
+}
+
+/// Tags are matched by their slug so they can be used in urls without encoding.
+pub fn tag_slug(tag: &str) -> String {
+    tag.trim().to_lowercase().replace(char::is_whitespace, "-")
+}
+
+/// The front matter every collection entry is reduced to for listings, feeds and cards.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Entry {
+    pub(crate) url: String,
+    pub(crate) name: String,
+    pub(crate) date: NaiveDate,
+    pub(crate) description: String,
+    pub(crate) tags: Vec<String>,
+    /// From the front matter, or the last commit or modification time of the file.
+    pub(crate) updated: Option<NaiveDate>,
+    /// The first commit of the file.
+    pub(crate) published: Option<NaiveDate>,
+    pub(crate) lifecycle: Lifecycle,
+    /// Words of the body, code blocks excluded.
+    pub(crate) words: usize,
+}
+
+/// Reading speed the reading times are estimated with.
+const WORDS_PER_MINUTE: usize = 200;
+
+impl Entry {
+    pub fn reading_minutes(&self) -> usize {
+        self.words.div_ceil(WORDS_PER_MINUTE).max(1)
+    }
+
+    /// The last update, if it came after the entry was published.
+    pub fn updated_after_publishing(&self) -> Option<NaiveDate> {
+        self.updated.filter(|updated| *updated > self.date)
+    }
+}
+
+/// The entries around one entry, shown below it.
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct Neighbours {
+    /// The entry dated before it.
+    pub(crate) previous: Option<Entry>,
+    /// The entry dated after it.
+    pub(crate) next: Option<Entry>,
+    pub(crate) related: Vec<Entry>,
+}
+
+#[server(endpoint = "entries", input = GetUrl, output = Cbor)]
+pub async fn get_entries(collection: Collection) -> Result<Vec<Entry>, AppError> {
+    crate::caching::cache_server_fn::<GetEntries>().await;
+    Ok(ssr::read_entries(collection).await)
+}
+
+#[server(endpoint = "entry", input = GetUrl, output = Cbor)]
+pub async fn get_entry(collection: Collection, slug: String) -> Result<(Entry, String), AppError> {
+    let (entry, content) = ssr::read_entry(collection, &slug)
+        .await
+        .inspect_err(AppError::set_response_status)?;
+    crate::caching::cache_server_fn::<GetEntry>().await;
+    let html = crate::images::rewrite_images(
+        &markdown::to_html(&content),
+        std::path::Path::new(collection.config().dir),
+    )
+    .await;
+    Ok((entry, html))
+}
+
+#[server(endpoint = "neighbours", input = GetUrl, output = Cbor)]
+pub async fn get_neighbours(collection: Collection, slug: String) -> Result<Neighbours, AppError> {
+    crate::caching::cache_server_fn::<GetNeighbours>().await;
+    let url = format!("/{}/{}", collection.config().route_prefix, slug);
+    Ok(ssr::neighbours(ssr::read_entries(collection).await, &url))
+}
+
+#[cfg(feature = "ssr")]
+pub mod ssr {
+    use super::{tag_slug, Collection, Entry, Neighbours, SortOrder};
+    use crate::error_template::AppError;
+    use crate::history::ssr::{commit_dates, CommitDates};
+    use serde::de::DeserializeOwned;
+    use std::collections::HashMap;
+    use std::path::{Path, PathBuf};
+
+    /// Related entries shown below an entry.
+    const RELATED: usize = 3;
+
+    /// The typed front matter of a collection, converted into the shared [`Entry`].
+    pub trait FrontMatter: DeserializeOwned {
+        fn into_entry(self, url: String, words: usize) -> Entry;
+    }
+
+    /// Counts the words of a markdown body, fenced code is not read like prose.
+    fn word_count(markdown: &str) -> usize {
+        let mut in_code = false;
+        markdown
+            .lines()
+            .filter(|line| {
+                if line.trim_start().starts_with("```") {
+                    in_code = !in_code;
+                    return false;
+                }
+                !in_code
+            })
+            .flat_map(str::split_whitespace)
+            .filter(|word| word.chars().any(char::is_alphanumeric))
+            .count()
+    }
+
+    /// Fills in the dates of the commits of `path`, the update date falls back to its
+    /// modification time when it is not committed and the front matter has none.
+    async fn with_dates(
+        mut entry: Entry,
+        path: &Path,
+        commits: &HashMap<PathBuf, CommitDates>,
+    ) -> Entry {
+        let committed = commits.get(path.strip_prefix(".").unwrap_or(path));
+        entry.published = committed.map(|dates| dates.first);
+        if entry.updated.is_none() {
+            entry.updated = match committed {
+                Some(dates) => Some(dates.last),
+                None => tokio::fs::metadata(path)
+                    .await
+                    .and_then(|m| m.modified())
+                    .ok()
+                    .map(|modified| chrono::DateTime::<chrono::Utc>::from(modified).date_naive()),
+            };
+        }
+        entry
+    }
+
+    fn parse<F: FrontMatter>(url: String, content: &str) -> Result<(Entry, String), AppError> {
+        let matter = gray_matter::Matter::<gray_matter::engine::YAML>::new();
+        let result = matter
+            .parse::<F>(content)
+            .map_err(|e| AppError::ContentInvalid(e.to_string()))?;
+        let data = result
+            .data
+            .ok_or_else(|| AppError::ContentInvalid("missing front matter".to_string()))?;
+        let words = word_count(&result.content);
+        Ok((data.into_entry(url, words), result.content))
+    }
+
+    /// Parses an entry file, returning the entry and its markdown body.
+    pub fn parse_entry(
+        collection: Collection,
+        slug: &str,
+        content: &str,
+    ) -> Result<(Entry, String), AppError> {
+        let url = format!("/{}/{}", collection.config().route_prefix, slug);
+        match collection {
+            Collection::Projects => parse::<crate::projects::ssr::ProjectFrontMatter>(url, content),
+            Collection::Blog => parse::<crate::blog::ssr::PostFrontMatter>(url, content),
+        }
+    }
+
+    /// The path of the file of an entry, whether it exists or not.
+    pub fn entry_path(collection: Collection, slug: &str) -> PathBuf {
+        let config = collection.config();
+        let mut path = PathBuf::from(config.dir).join("file");
+        path.set_file_name(slug);
+        path.set_extension(config.extension);
+        path
+    }
+
+    /// Reads and parses a single entry by its slug, slugs that could leave the collection
+    /// directory are not found.
+    pub async fn read_entry(
+        collection: Collection,
+        slug: &str,
+    ) -> Result<(Entry, String), AppError> {
+        if slug.is_empty() || slug.contains(['/', '\\']) || slug.starts_with('.') {
+            return Err(AppError::NotFound);
+        }
+        let path = entry_path(collection, slug);
+        let content = tokio::fs::read_to_string(&path)
+            .await
+            .map_err(|e| match e.kind() {
+                std::io::ErrorKind::NotFound => AppError::NotFound,
+                _ => AppError::Internal(format!("reading {}: {}", path.display(), e)),
+            })?;
+        let (entry, content) = parse_entry(collection, slug, &content).inspect_err(|e| {
+            tracing::warn!("invalid entry {}: {}", path.display(), e);
+        })?;
+        let commits = commit_dates(Path::new(collection.config().dir)).await;
+        Ok((with_dates(entry, &path, &commits).await, content))
+    }
+
+    /// The files of a collection as `(slug, path)`, whether they parse or not.
+    pub fn entry_files(collection: Collection) -> Vec<(String, PathBuf)> {
+        let config = collection.config();
+        let Ok(paths) = std::fs::read_dir(config.dir) else {
+            return Vec::new();
+        };
+        paths
+            .filter_map(|p| p.ok())
+            .filter_map(|p| {
+                let name = p.file_name().into_string().ok()?;
+                let slug = name.strip_suffix(&format!(".{}", config.extension))?;
+                Some((slug.to_string(), p.path()))
+            })
+            .collect()
+    }
+
+    /// Reads every entry of the collection in its configured sort order.
+    ///
+    /// Files that fail to parse are logged and left out of the listing.
+    pub async fn read_entries(collection: Collection) -> Vec<Entry> {
+        let config = collection.config();
+        metrics::counter!("content_index_reloads_total", "content" => config.route_prefix)
+            .increment(1);
+        let commits = &commit_dates(Path::new(config.dir)).await;
+        let mut entries = futures::future::join_all(
+            entry_files(collection)
+                .into_iter()
+                .map(|(slug, path)| async move {
+                    let content = tokio::fs::read_to_string(&path).await.ok()?;
+                    match parse_entry(collection, &slug, &content) {
+                        Ok((entry, _)) => Some(with_dates(entry, &path, commits).await),
+                        Err(e) => {
+                            tracing::warn!("skipping invalid entry {}: {}", path.display(), e);
+                            metrics::counter!(
+                                "content_index_failures_total",
+                                "content" => collection.config().route_prefix
+                            )
+                            .increment(1);
+                            None
+                        }
+                    }
+                })
+                .collect::<Vec<_>>(),
+        )
+        .await
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>();
+        match config.sort {
+            SortOrder::NewestFirst => entries.sort_by_key(|e| std::cmp::Reverse(e.date)),
+            SortOrder::OldestFirst => entries.sort_by_key(|e| e.date),
+        }
+        entries
+    }
+
+    /// Finds the entries dated around the one at `url` and the ones related to it, ranked by
+    /// the tags they share and then by how close their dates are.
+    pub fn neighbours(mut entries: Vec<Entry>, url: &str) -> Neighbours {
+        entries.sort_by_key(|e| e.date);
+        let Some(index) = entries.iter().position(|e| e.url == url) else {
+            return Neighbours::default();
+        };
+        let entry = &entries[index];
+        let tags = entry.tags.iter().map(|t| tag_slug(t)).collect::<Vec<_>>();
+        let mut related = entries
+            .iter()
+            .filter(|e| e.url != url)
+            .map(|e| {
+                let shared = e
+                    .tags
+                    .iter()
+                    .filter(|t| tags.contains(&tag_slug(t)))
+                    .count();
+                (shared, (e.date - entry.date).num_days().abs(), e)
+            })
+            .filter(|&(shared, _, _)| shared > 0)
+            .collect::<Vec<_>>();
+        related.sort_by_key(|&(shared, days, _)| (std::cmp::Reverse(shared), days));
+        Neighbours {
+            previous: index.checked_sub(1).map(|i| entries[i].clone()),
+            next: entries.get(index + 1).cloned(),
+            related: related
+                .into_iter()
+                .take(RELATED)
+                .map(|(_, _, e)| e.clone())
+                .collect(),
+        }
+    }
+}
+
+/// Lists the entries of a collection, narrowed to a tag when the route has a `tag` parameter.
+#[component]
+pub fn Listing(collection: Collection) -> impl IntoView {
+    let config = collection.config();
+    let params = use_params_map();
+    let tag = move || params.with(|params| params.get("tag"));
+    let query = use_query_map();
+    let status = move || {
+        query
+            .with(|query| query.get("status"))
+            .and_then(|status| status.parse::<Status>().ok())
+    };
+    let once = Resource::new(|| (), move |_| async move { get_entries(collection).await });
+    view! {
+        <Title text="Lukas Hermansson"/>
+        <Meta property="og:title" content=config.title/>
+        <Meta property="og:description" content=config.description/>
+        <Meta property="og:image" content=format!("{}/assets/og-card.jpg", crate::SITE_URL)/>
+        <Meta property="og:type" content="website"/>
+        <Link rel="alternate" type_="application/rss+xml" title=config.title href=collection.feed_url()/>
+        <div class="m-auto md:w-3/5 w-full max-md:m-2  flex flex-col text-gray-700 dark:text-gray-400 ">
+            {move || tag().map(|tag| view! {
+                <h1 class="text-3xl font-bold mt-4">{format!("Tagged: {}", tag)}</h1>
+            })}
+            <div class="place-content-around grid mt-2 gap-4 grid-flow-row grid-cols-1 lg:grid-cols-2">
+                <Suspense>
+                    {move || match once.get() {
+                        None => view! { <ListingPlaceholder/> }.into_any(),
+                        Some(data) => {
+                            let tag = tag();
+                            let status = status();
+                            let base = match &tag {
+                                Some(tag) => collection.tag_url(tag),
+                                None => format!("/{}", config.route_prefix),
+                            };
+                            view! {
+                                <ErrorBoundary fallback=error_template>
+                                    {data.map(|entries| {
+                                        let entries = entries
+                                            .into_iter()
+                                            .filter(|n| match &tag {
+                                                Some(tag) => n.tags.iter().any(|t| &tag_slug(t) == tag),
+                                                None => true,
+                                            })
+                                            .collect::<Vec<_>>();
+                                        let counts = Status::ALL
+                                            .into_iter()
+                                            .map(|s| (s, entries.iter().filter(|e| e.lifecycle.status == Some(s)).count()))
+                                            .filter(|&(_, count)| count > 0)
+                                            .collect::<Vec<_>>();
+                                        view! {
+                                            <StatusFilter base counts current=status/>
+                                            {entries
+                                                .into_iter()
+                                                .filter(|e| status.is_none() || e.lifecycle.status == status)
+                                                .map(|entry| view! { <EntryCard collection entry/> })
+                                                .collect::<Vec<_>>()}
+                                        }
+                                    })}
+                                </ErrorBoundary>
+                            }
+                                .into_any()
+                        }
+                    }}
+
+                </Suspense>
+            </div>
+        </div>
+    }
+}
+/// Links narrowing a listing at `base` to the statuses its entries have, nothing when none has
+/// one.
+#[component]
+fn StatusFilter(
+    base: String,
+    counts: Vec<(Status, usize)>,
+    current: Option<Status>,
+) -> impl IntoView {
+    let link = move |href: String, label: String, selected: bool| {
+        view! {
+            // `A` marks every link as current, they only differ in their query
+            <a
+                href=href
+                class=if selected { "font-bold" } else { "hover:underline" }
+                aria-current=selected.then_some("page")
+            >
+                {label}
+            </a>
+        }
+    };
+    (!counts.is_empty()).then(|| {
+        view! {
+            <nav class="col-span-full flex flex-wrap gap-3 text-sm">
+                {link(base.clone(), "All".to_string(), current.is_none())}
+                {counts
+                    .into_iter()
+                    .map(|(status, count)| {
+                        link(
+                            format!("{}?status={}", base, status.as_str()),
+                            format!("{} ({})", status.label(), count),
+                            current == Some(status),
+                        )
+                    })
+                    .collect::<Vec<_>>()}
+            </nav>
+        }
+    })
+}
+
+#[component]
+fn EntryCard(collection: Collection, entry: Entry) -> impl IntoView {
+    let date = crate::locale::format_date(entry.date, crate::locale::use_locale());
+    let stats = view! { <EntryStats entry=entry.clone()/> };
+    view! {
+        <div class="p-3 flex flex-col rounded shadow-md shadow-gray-300 dark:shadow-gray-950 bg-white dark:bg-slate-800 rounded shadow-md shadow-gray-300 dark:shadow-gray-950">
+            <A href=entry.url>
+                <h2 class="text-2xl font-bold">
+                    {entry.name}
+                    <time
+                        datetime=entry.date.to_string()
+                        class="italic block float-right opacity-75 font-light ml-1"
+                    >
+                        {date}
+                    </time>
+                </h2>
+                <p>{entry.description}</p>
+                <LifecycleLine lifecycle=entry.lifecycle/>
+                <p class="text-sm opacity-75 mt-1">{stats}</p>
+            </A>
+            <div>
+                {entry
+                    .tags
+                    .into_iter()
+                    .map(|n| {
+                        view! {
+                            <A href=collection.tag_url(&n) {..} class="inline-block bg-slate-100 dark:bg-slate-900 rounded m-1 p-1">{n}</A>
+                        }
+                    })
+                    .collect::<Vec<_>>()}
+            </div>
+        </div>
+    }
+}
+/// The reading time, word count and update date of an entry, after its date when `dated`.
+#[component]
+fn EntryStats(entry: Entry, #[prop(optional)] dated: bool) -> impl IntoView {
+    let locale = crate::locale::use_locale();
+    let mut stats = Vec::new();
+    if dated {
+        stats.push(crate::locale::format_date(entry.date, locale));
+    }
+    stats.push(format!("{} min read", entry.reading_minutes()));
+    stats.push(format!("{} words", entry.words));
+    if let Some(updated) = entry.updated_after_publishing() {
+        stats.push(format!(
+            "Updated {}",
+            crate::locale::format_date(updated, locale)
+        ));
+    }
+    stats.join(" · ")
+}
+
+#[component]
+fn listing_placeholder() -> impl IntoView {
+    (0..6)
+                                    .map(|_| {
+                                        view! {
+                                            <div class="p-3 flex flex-col rounded shadow-md shadow-gray-300 dark:shadow-gray-950 bg-white dark:bg-slate-800 rounded shadow-md shadow-gray-300 dark:shadow-gray-950 animate-pulse">
+                                                <div class="flex flex-row mb-2">
+                                                    <div class="h-5 w-40 bg-gray-400 rounded"></div>
+                                                    <div class="opacity-75 ml-1 h-5 w-32 bg-gray-400 rounded"></div>
+                                                </div>
+                                                <div class="h-3 max-w-30 bg-gray-400 rounded mb-2"></div>
+                                                <div>
+                                                    {(0..3)
+                                                        .map(|_| {
+                                                            view! {
+                                                                <div class="inline-block bg-slate-100 dark:bg-slate-900 rounded m-1 p-1 h-5 w-10"></div>
+                                                            }
+                                                        })
+                                                        .collect::<Vec<_>>()}
+                                                </div>
+                                            </div>
+                                        }
+                                    })
+                                    .collect::<Vec<_>>()
+}
+/// Renders a single entry of a collection, the slug is taken from the `id` route parameter.
+#[component]
+pub fn Detail(collection: Collection) -> impl IntoView {
+    let params = use_params_map();
+    let id = move || params.with(|params| params.get("id")).unwrap();
+    let resource =
+        Resource::new_blocking(
+            id,
+            move |arg| async move { get_entry(collection, arg).await },
+        );
+
+    view! {
+        <div class="m-auto md:w-3/5 w-full max-md:m-2 flex flex-col text-gray-700 dark:text-gray-400 ">
+            <Suspense>
+                {move || match resource.get() {
+                    None => view! { <p>"Loading..."</p> }.into_any(),
+                    Some(data) => {
+                        view! {
+                            <ErrorBoundary fallback=error_template>
+                                {data.map(|(entry, data)| {
+                                    let title = entry.name.clone();
+                                    let og_image = collection.og_image_url(&id());
+                                    let last_updated = view! { <LastUpdated entry=entry.clone()/> };
+                                    view! {
+                                        <Meta property="og:title" content=format!("{}", &title)/>
+                                        <Meta
+                                            property="og:description"
+                                            content=format!("{}: {}", collection.config().item_label, &title)
+                                        />
+                                        <Meta property="og:type" content="website"/>
+                                        <Meta property="og:image" content=og_image/>
+                                        <Title text=format!("Lukas Hermansson - {}", &title)/>
+                                        <h1 class="text-4xl mt-3 font-bold">{title}</h1>
+                                        <LifecycleLine lifecycle=entry.lifecycle/>
+                                        <p class="opacity-75 mb-3">
+                                            <EntryStats entry dated=true/>
+                                        </p>
+                                        <div class="post" inner_html=data></div>
+                                        {last_updated}
+                                        <EntryNeighbours collection slug=id()/>
+                                    }
+                                })}
+                            </ErrorBoundary>
+                        }
+                            .into_any()
+                    }
+                }}
+
+            </Suspense>
+        </div>
+    }
+}
+
+/// Previous and next links in date order and the related entries, below an entry.
+#[component]
+fn EntryNeighbours(collection: Collection, slug: String) -> impl IntoView {
+    let neighbours = Resource::new(
+        move || slug.clone(),
+        move |slug| async move { get_neighbours(collection, slug).await },
+    );
+    let config = collection.config();
+    view! {
+        <Suspense>
+            {move || {
+                neighbours
+                    .get()
+                    .and_then(Result::ok)
+                    .map(|neighbours| {
+                        view! {
+                            <nav class="flex justify-between gap-4 my-6 border-t border-dashed border-gray-400 dark:border-gray-500 pt-4">
+                                {neighbours.previous.map(|entry| view! {
+                                    <A href=entry.url {..} class="hover:underline" rel="prev">
+                                        {format!("← {}", entry.name)}
+                                    </A>
+                                })}
+                                <span></span>
+                                {neighbours.next.map(|entry| view! {
+                                    <A href=entry.url {..} class="hover:underline text-right" rel="next">
+                                        {format!("{} →", entry.name)}
+                                    </A>
+                                })}
+                            </nav>
+                            {(!neighbours.related.is_empty()).then(|| view! {
+                                <h2 class="text-2xl font-bold my-2">{format!("Related {}s", config.item_label)}</h2>
+                                <div class="grid gap-4 grid-flow-row grid-cols-1 lg:grid-cols-2 mb-4">
+                                    {neighbours
+                                        .related
+                                        .into_iter()
+                                        .map(|entry| view! { <EntryCard collection entry/> })
+                                        .collect::<Vec<_>>()}
+                                </div>
+                            })}
+                        }
+                    })
+            }}
+        </Suspense>
+    }
+}
+
+#[cfg(all(test, feature = "ssr"))]
+mod tests {
+    use super::*;
+
+    fn read_entry(collection: Collection, slug: &str) -> Result<(Entry, String), AppError> {
+        tokio::runtime::Runtime::new()
+            .unwrap()
+            .block_on(ssr::read_entry(collection, slug))
+    }
+
+    #[test]
+    fn reads_entries_by_slug() {
+        let (entry, _) = read_entry(Collection::Projects, "kubernetes").unwrap();
+        assert_eq!(entry.url, "/projects/kubernetes");
+    }
+
+    #[test]
+    fn rejects_slugs_outside_the_collection() {
+        for slug in [
+            "../pages/about",
+            "../../pages/about",
+            "..\\pages\\about",
+            "introducing-the-blog/../introducing-the-blog",
+            "..",
+            ".hidden",
+            "",
+        ] {
+            assert!(
+                matches!(read_entry(Collection::Blog, slug), Err(AppError::NotFound)),
+                "{:?}",
+                slug
+            );
+        }
+    }
+}
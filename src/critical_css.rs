@@ -0,0 +1,346 @@
+use crate::bundle::Bundle;
+use axum::extract::MatchedPath;
+use leptos::config::LeptosOptions;
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::{Arc, LazyLock, OnceLock, RwLock};
+
+/// Html at the start of the body, and of the streamed fragments, treated as above the fold.
+const ABOVE_THE_FOLD: usize = 16 * 1024;
+/// Subsets larger than this are not inlined, they would cost more than the blocking request.
+const MAX_INLINE: usize = 32 * 1024;
+/// At-rules whose blocks are subset, the others are kept whole.
+const GROUPING_RULES: &[&str] = &["media", "supports", "layer", "container"];
+
+/// The critical css of a route with the classes it was subset for.
+struct RouteCss {
+    classes: HashSet<String>,
+    css: Option<Arc<str>>,
+}
+
+/// Learnt from the responses of each route, the first response of a route has no critical css.
+static ROUTES: LazyLock<RwLock<HashMap<String, RouteCss>>> = LazyLock::new(Default::default);
+
+fn stylesheet(options: &LeptosOptions) -> Option<&'static str> {
+    static STYLESHEET: OnceLock<Option<String>> = OnceLock::new();
+    STYLESHEET
+        .get_or_init(|| {
+            let path = PathBuf::from(options.site_root.as_ref())
+                .join(Bundle::get(options).css.trim_start_matches('/'));
+            let css = std::fs::read_to_string(&path);
+            if let Err(e) = &css {
+                tracing::warn!(
+                    "not inlining critical css, reading {}: {}",
+                    path.display(),
+                    e
+                );
+            }
+            css.ok()
+        })
+        .as_deref()
+}
+
+/// The critical css of the route being rendered, call it in the shell.
+pub fn current() -> Option<Arc<str>> {
+    let parts = leptos::prelude::use_context::<http::request::Parts>()?;
+    let route = parts.extensions.get::<MatchedPath>()?;
+    let routes = ROUTES.read().unwrap_or_else(|e| e.into_inner());
+    routes.get(route.as_str())?.css.clone()
+}
+
+/// Records the classes used above the fold in a rendered page of `route`.
+pub fn learn(options: &LeptosOptions, route: &str, html: &[u8]) {
+    let Some(stylesheet) = stylesheet(options) else {
+        return;
+    };
+    let html = String::from_utf8_lossy(html);
+    let Some(body) = html.find("<body") else {
+        return;
+    };
+    let mut classes = used_classes(start(&html[body..]));
+    // Resources resolved while streaming are sent after the document and replace fallbacks that
+    // may be at the top of the page
+    if let Some(end) = html.find("</html>") {
+        classes.extend(used_classes(start(&html[end..])));
+    }
+
+    let known = ROUTES.read().unwrap_or_else(|e| e.into_inner());
+    if known
+        .get(route)
+        .is_some_and(|known| classes.is_subset(&known.classes))
+    {
+        return;
+    }
+    drop(known);
+
+    let mut routes = ROUTES.write().unwrap_or_else(|e| e.into_inner());
+    let entry = routes.entry(route.to_string()).or_insert(RouteCss {
+        classes: HashSet::new(),
+        css: None,
+    });
+    entry.classes.extend(classes);
+    let css = subset(stylesheet, &entry.classes);
+    // The css ends up inside a <style> element
+    entry.css = (css.len() <= MAX_INLINE && !css.contains("</")).then(|| css.into());
+}
+
+/// The part of `html` treated as above the fold.
+fn start(html: &str) -> &str {
+    let mut end = html.len().min(ABOVE_THE_FOLD);
+    while !html.is_char_boundary(end) {
+        end -= 1;
+    }
+    &html[..end]
+}
+
+/// The classes of the `class` attributes in `html`.
+fn used_classes(html: &str) -> HashSet<String> {
+    html.split(" class=\"")
+        .skip(1)
+        .filter_map(|rest| rest.split_once('"'))
+        .flat_map(|(value, _)| value.split_whitespace())
+        .map(|class| class.replace("&amp;", "&"))
+        .collect()
+}
+
+/// Keeps the rules of `css` that can match with only `classes` present, rules without class
+/// selectors are always kept.
+fn subset(css: &str, classes: &HashSet<String>) -> String {
+    let mut out = String::new();
+    let mut rest = css;
+    while let Some((prelude, block, tail)) = next_item(rest) {
+        rest = tail;
+        let Some(block) = block else {
+            out.push_str(prelude);
+            out.push(';');
+            continue;
+        };
+        let keep = match prelude.strip_prefix('@') {
+            Some(at_rule) => {
+                let name = at_rule
+                    .split(|c: char| !c.is_alphanumeric() && c != '-')
+                    .next();
+                if GROUPING_RULES.contains(&name.unwrap_or_default()) {
+                    let inner = subset(block, classes);
+                    if !inner.is_empty() {
+                        out.push_str(&format!("{}{{{}}}", prelude, inner));
+                    }
+                    continue;
+                }
+                true
+            }
+            None => selector_matches(prelude, classes),
+        };
+        if keep {
+            out.push_str(&format!("{}{{{}}}", prelude, block));
+        }
+    }
+    out
+}
+
+/// Splits off the next rule of `css`: its prelude, its block unless it is a statement ending
+/// in `;`, and the css after it. Comments between rules are skipped.
+fn next_item(css: &str) -> Option<(&str, Option<&str>, &str)> {
+    let mut css = css.trim_start();
+    while let Some(comment) = css.strip_prefix("/*") {
+        css = comment.split_once("*/")?.1.trim_start();
+    }
+    let bytes = css.as_bytes();
+    let mut depth = 0;
+    let mut start = None;
+    let mut quote = None;
+    let mut i = 0;
+    while i < bytes.len() {
+        match (bytes[i], quote) {
+            (b'\\', _) => i += 1,
+            (q, Some(open)) if q == open => quote = None,
+            (_, Some(_)) => {}
+            (q @ (b'"' | b'\''), None) => quote = Some(q),
+            (b'/', None) if bytes.get(i + 1) == Some(&b'*') => {
+                i += css[i..].find("*/")? + 1;
+            }
+            (b';', None) if depth == 0 => return Some((css[..i].trim(), None, &css[i + 1..])),
+            (b'{', None) => {
+                if depth == 0 {
+                    start = Some(i);
+                }
+                depth += 1;
+            }
+            (b'}', None) => {
+                depth -= 1;
+                if depth == 0 {
+                    let start = start?;
+                    return Some((css[..start].trim(), Some(&css[start + 1..i]), &css[i + 1..]));
+                }
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    None
+}
+
+/// Whether one of the selectors in the list only needs classes from `classes`, classes inside
+/// functional pseudo-classes like `:not()` are not required.
+fn selector_matches(selectors: &str, classes: &HashSet<String>) -> bool {
+    let mut required = Vec::new();
+    let mut depth = 0;
+    let mut chars = selectors.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                chars.next();
+            }
+            '(' | '[' => depth += 1,
+            ')' | ']' => depth -= 1,
+            ',' if depth == 0 => {
+                if required.iter().all(|class| classes.contains(class)) {
+                    return true;
+                }
+                required.clear();
+            }
+            '.' if depth == 0 => {
+                let mut class = String::new();
+                while let Some(&c) = chars.peek() {
+                    if c == '\\' {
+                        chars.next();
+                        class.push(unescape(&mut chars));
+                    } else if c.is_alphanumeric() || c == '-' || c == '_' || !c.is_ascii() {
+                        class.push(c);
+                        chars.next();
+                    } else {
+                        break;
+                    }
+                }
+                required.push(class);
+            }
+            _ => {}
+        }
+    }
+    required.iter().all(|class| classes.contains(class))
+}
+
+/// The character of an escape sequence in an identifier, after its backslash.
+fn unescape(chars: &mut std::iter::Peekable<std::str::Chars>) -> char {
+    let mut hex = String::new();
+    while let Some(&c) = chars.peek() {
+        if hex.len() == 6 || !c.is_ascii_hexdigit() {
+            break;
+        }
+        hex.push(c);
+        chars.next();
+    }
+    if hex.is_empty() {
+        return chars.next().unwrap_or('\u{FFFD}');
+    }
+    if chars.peek().is_some_and(|c| c.is_ascii_whitespace()) {
+        chars.next();
+    }
+    u32::from_str_radix(&hex, 16)
+        .ok()
+        .and_then(char::from_u32)
+        .unwrap_or('\u{FFFD}')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn classes(classes: &[&str]) -> HashSet<String> {
+        classes.iter().map(|class| class.to_string()).collect()
+    }
+
+    fn items(mut css: &str) -> Vec<(&str, Option<&str>)> {
+        let mut items = Vec::new();
+        while let Some((prelude, block, rest)) = next_item(css) {
+            items.push((prelude, block));
+            css = rest;
+        }
+        items
+    }
+
+    #[test]
+    fn splits_rules_and_statements() {
+        assert_eq!(
+            items("@charset \"utf-8\";\n.a { color: red }\n@media (width > 1px) { .b { x: y } }"),
+            [
+                ("@charset \"utf-8\"", None),
+                (".a", Some(" color: red ")),
+                ("@media (width > 1px)", Some(" .b { x: y } ")),
+            ]
+        );
+    }
+
+    #[test]
+    fn skips_comments() {
+        assert_eq!(
+            items("/* a { } */ .a { x: y } /* ; */ /**/ .b /* { */ { x: /* } */ y }"),
+            [
+                (".a", Some(" x: y ")),
+                (".b /* { */", Some(" x: /* } */ y "))
+            ]
+        );
+        assert_eq!(items("/* unterminated .a { x: y }"), []);
+    }
+
+    #[test]
+    fn ignores_braces_in_strings_and_escapes() {
+        assert_eq!(
+            items(".a::after { content: \"}\" } .b\\{ { content: '\\'{' }"),
+            [
+                (".a::after", Some(" content: \"}\" ")),
+                (".b\\{", Some(" content: '\\'{' "))
+            ]
+        );
+    }
+
+    #[test]
+    fn matches_selectors_with_used_classes() {
+        let used = classes(&["a", "b"]);
+        assert!(selector_matches(".a", &used));
+        assert!(selector_matches(".a.b > p", &used));
+        assert!(selector_matches("body", &used));
+        assert!(selector_matches(".c, .a", &used));
+        assert!(!selector_matches(".a.c", &used));
+        assert!(!selector_matches(".c, .d .a", &used));
+    }
+
+    #[test]
+    fn ignores_classes_in_functional_pseudo_classes_and_attributes() {
+        let used = classes(&["a"]);
+        assert!(selector_matches(".a:not(.c)", &used));
+        assert!(selector_matches(":is(.c, .d) .a", &used));
+        assert!(selector_matches("[data-x=\".c\"]", &used));
+        assert!(!selector_matches(".c:not(.a)", &used));
+    }
+
+    #[test]
+    fn unescapes_class_names() {
+        let used = classes(&["md:flex", "w-1/2", "a.b", "é"]);
+        assert!(selector_matches(".md\\:flex", &used));
+        assert!(selector_matches(".w-1\\/2", &used));
+        assert!(selector_matches(".a\\.b", &used));
+        assert!(selector_matches(".\\e9 ", &used));
+        assert!(selector_matches(".\\0000e9", &used));
+        assert!(!selector_matches(".md\\:grid", &used));
+    }
+
+    #[test]
+    fn subsets_grouping_rules() {
+        let css = "@import url(a.css);.a{x:y}.b{x:y}@media (min-width:1px){.a{x:z}.b{x:z}}\
+                   @supports (x:y){.b{x:y}}@font-face{font-family:c}@keyframes k{0%{x:y}}";
+        assert_eq!(
+            subset(css, &classes(&["a"])),
+            "@import url(a.css);.a{x:y}@media (min-width:1px){.a{x:z}}\
+             @font-face{font-family:c}@keyframes k{0%{x:y}}"
+        );
+    }
+
+    #[test]
+    fn finds_used_classes() {
+        assert_eq!(
+            used_classes("<p class=\"a  b\"><i class=\"c&amp;d\"></i><b data-class=\"e\"></b>"),
+            classes(&["a", "b", "c&d"])
+        );
+    }
+}
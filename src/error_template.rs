@@ -1,28 +1,106 @@
+use http::StatusCode;
 use leptos::prelude::*;
+use leptos::server_fn::codec::JsonEncoding;
+use leptos::server_fn::error::{FromServerFnError, ServerFnErrorErr};
+use leptos_meta::Title;
+use serde::{Deserialize, Serialize};
 
-// A basic function to display errors served by the error boundaries. Feel free to do more complicated things
-// here than just displaying them
-pub fn error_template(errors: Option<RwSignal<Errors>>) -> AnyView {
-    let Some(errors) = errors else {
-        panic!("No Errors found and we expected errors!");
-    };
+/// The errors of the application, returned by the server functions and rendered by [`ErrorPage`].
+#[derive(Clone, Debug, PartialEq, Eq, thiserror::Error, Serialize, Deserialize)]
+pub enum AppError {
+    #[error("not found")]
+    NotFound,
+    #[error("bad request: {0}")]
+    BadRequest(String),
+    /// A content file exists but could not be parsed.
+    #[error("invalid content: {0}")]
+    ContentInvalid(String),
+    #[error("internal error: {0}")]
+    Internal(String),
+}
 
-    view! {
-      <h1>"Errors"</h1>
-      <For
-          // a function that returns the items we're iterating over; a signal is fine
-          each=move ||  errors.get()
-          // a unique key for each item as a reference
-          key=|(key, _)| key.clone()
-          // renders each item to a view
-          children=move | (_, error)| {
-          let error_string = error.to_string();
-            view! {
-
-              <p>"Error: " {error_string}</p>
+impl AppError {
+    pub fn status_code(&self) -> StatusCode {
+        match self {
+            AppError::NotFound => StatusCode::NOT_FOUND,
+            AppError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            AppError::ContentInvalid(_) | AppError::Internal(_) => {
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+        }
+    }
+
+    /// Sets the status code of the response being rendered, if any.
+    pub fn set_response_status(&self) {
+        #[cfg(feature = "ssr")]
+        if let Some(response) = use_context::<leptos_axum::ResponseOptions>() {
+            response.set_status(self.status_code());
+        }
+    }
+
+    fn title(&self) -> &'static str {
+        match self {
+            AppError::NotFound => "Page not found",
+            AppError::BadRequest(_) => "Bad request",
+            AppError::ContentInvalid(_) | AppError::Internal(_) => "Something went wrong",
+        }
+    }
+
+    fn message(&self) -> &'static str {
+        match self {
+            AppError::NotFound => "The page you are looking for does not exist.",
+            AppError::BadRequest(_) => "The request could not be understood.",
+            AppError::ContentInvalid(_) => "This page could not be rendered.",
+            AppError::Internal(_) => "The server failed to handle the request.",
+        }
+    }
+}
+
+impl FromServerFnError for AppError {
+    type Encoder = JsonEncoding;
+
+    fn from_server_fn_error(value: ServerFnErrorErr) -> Self {
+        match value {
+            ServerFnErrorErr::Args(err) | ServerFnErrorErr::MissingArg(err) => {
+                AppError::BadRequest(err)
             }
-          }
-      />
+            err => AppError::Internal(err.to_string()),
+        }
+    }
+}
+
+/// A full page error, it also sets the status code of the response during SSR.
+#[component]
+pub fn ErrorPage(error: AppError) -> impl IntoView {
+    error.set_response_status();
+    let status = error.status_code();
+    view! {
+        <Title text=format!("Lukas Hermansson - {}", error.title())/>
+        <div class="col-span-full w-full flex flex-col items-center text-center text-gray-700 dark:text-gray-400">
+            <h1 class="text-6xl font-bold mt-6">{status.as_u16()}</h1>
+            <h2 class="text-3xl font-bold m-4">{error.title()}</h2>
+            <p class="m-2">{error.message()}</p>
+            <a href="/" class="underline m-2">"Go to the home page"</a>
+        </div>
+    }
+}
+
+/// Fallback for the error boundaries, renders the first error as an [`ErrorPage`].
+///
+/// Errors that aren't an [`AppError`] are shown as internal errors.
+pub fn error_template(errors: ArcRwSignal<Errors>) -> impl IntoView {
+    move || {
+        let error = errors
+            .read()
+            .iter()
+            .next()
+            .map(|(_, error)| {
+                error
+                    .downcast_ref::<AppError>()
+                    .cloned()
+                    .unwrap_or_else(|| AppError::Internal(error.to_string()))
+            })
+            .unwrap_or(AppError::Internal("unknown error".to_string()));
+        view! { <ErrorPage error/> }
     }
-    .into_any()
 }
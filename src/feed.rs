@@ -0,0 +1,60 @@
+use crate::collections::{ssr::read_entries, Collection};
+use crate::SITE_URL;
+use axum::{
+    http::header,
+    response::{IntoResponse, Response},
+};
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Renders the RSS feed of a collection, served at [`Collection::feed_url`].
+pub async fn feed(collection: Collection) -> Response {
+    let config = collection.config();
+    let items = read_entries(collection)
+        .await
+        .into_iter()
+        .map(|entry| {
+            let link = format!("{}{}", SITE_URL, entry.url);
+            let categories = entry
+                .tags
+                .iter()
+                .map(|tag| format!("<category>{}</category>", escape(tag)))
+                .collect::<String>();
+            format!(
+                "<item><title>{}</title><link>{}</link><guid>{}</guid><pubDate>{}</pubDate><description>{}</description>{}</item>",
+                escape(&entry.name),
+                link,
+                link,
+                entry
+                    .date
+                    .and_hms_opt(0, 0, 0)
+                    .unwrap()
+                    .and_utc()
+                    .to_rfc2822(),
+                escape(&entry.description),
+                categories,
+            )
+        })
+        .collect::<String>();
+
+    let body = format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?><rss version="2.0" xmlns:atom="http://www.w3.org/2005/Atom"><channel><title>Lukas Hermansson - {}</title><link>{}/{}</link><description>{}</description><atom:link href="{}{}" rel="self" type="application/rss+xml"/>{}</channel></rss>"#,
+        escape(config.title),
+        SITE_URL,
+        config.route_prefix,
+        escape(config.description),
+        SITE_URL,
+        collection.feed_url(),
+        items,
+    );
+    (
+        [(header::CONTENT_TYPE, "application/rss+xml; charset=utf-8")],
+        body,
+    )
+        .into_response()
+}
@@ -0,0 +1,175 @@
+use crate::collections::{
+    ssr::{entry_files, parse_entry},
+    Collection,
+};
+use crate::pages::ssr::{page_files, parse_page};
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use leptos::config::LeptosOptions;
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use std::path::Path;
+use std::sync::{Arc, LazyLock, Mutex};
+
+/// Summary of the content on disk, every entry and page is parsed to validate it.
+struct ContentIndex {
+    /// The [`content_stamp`] it was read at.
+    stamp: String,
+    hash: String,
+    projects: usize,
+    errors: Vec<String>,
+}
+
+static INDEX: LazyLock<Mutex<Option<Arc<ContentIndex>>>> = LazyLock::new(Default::default);
+
+/// Changes whenever the index could, with HEAD or the size or modification time of a content
+/// file. Checking it only reads metadata.
+fn content_stamp() -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(crate::history::ssr::head().unwrap_or_default());
+    let mut files = Collection::ALL
+        .into_iter()
+        .flat_map(entry_files)
+        .chain(page_files())
+        .map(|(_, path)| path)
+        .collect::<Vec<_>>();
+    files.sort();
+    for path in files {
+        hasher.update(path.to_string_lossy().as_bytes());
+        if let Ok(metadata) = std::fs::metadata(&path) {
+            hasher.update(metadata.len().to_le_bytes());
+            hasher.update(format!("{:?}", metadata.modified().ok()));
+        }
+    }
+    format!("{:x}", hasher.finalize())
+}
+
+/// The index of the content, read again only when its [`content_stamp`] changes.
+async fn content_index() -> Arc<ContentIndex> {
+    let stamp = content_stamp();
+    if let Some(index) = &*INDEX.lock().unwrap_or_else(|e| e.into_inner()) {
+        if index.stamp == stamp {
+            return index.clone();
+        }
+    }
+    let index = Arc::new(read_content_index(stamp).await);
+    *INDEX.lock().unwrap_or_else(|e| e.into_inner()) = Some(index.clone());
+    index
+}
+
+async fn read_content_index(stamp: String) -> ContentIndex {
+    let mut hasher = Sha256::new();
+    let mut projects = 0;
+    let mut errors = Vec::new();
+
+    for collection in Collection::ALL {
+        let mut files = entry_files(collection);
+        files.sort();
+        for (slug, path) in files {
+            let content = match tokio::fs::read_to_string(&path).await {
+                Ok(content) => content,
+                Err(e) => {
+                    errors.push(format!("{}: {}", path.display(), e));
+                    continue;
+                }
+            };
+            hasher.update(path.to_string_lossy().as_bytes());
+            hasher.update(&content);
+            match parse_entry(collection, &slug, &content) {
+                Ok(_) if collection == Collection::Projects => projects += 1,
+                Ok(_) => {}
+                Err(e) => errors.push(format!("{}: {}", path.display(), e)),
+            }
+        }
+    }
+
+    let mut files = page_files();
+    files.sort();
+    for (url, path) in files {
+        let content = match tokio::fs::read_to_string(&path).await {
+            Ok(content) => content,
+            Err(e) => {
+                errors.push(format!("{}: {}", path.display(), e));
+                continue;
+            }
+        };
+        hasher.update(path.to_string_lossy().as_bytes());
+        hasher.update(&content);
+        if let Err(e) = parse_page(url, &content) {
+            errors.push(format!("{}: {}", path.display(), e));
+        }
+    }
+
+    ContentIndex {
+        stamp,
+        hash: format!("{:x}", hasher.finalize()),
+        projects,
+        errors,
+    }
+}
+
+/// A hash of every entry and page on disk.
+pub async fn content_hash() -> String {
+    content_index().await.hash.clone()
+}
+
+/// Problems with the content on disk, reported by `/readyz` and the `validate` command.
+pub async fn content_errors() -> Vec<String> {
+    content_index().await.errors.clone()
+}
+
+/// The process is up and serving requests.
+pub async fn healthz() -> Response {
+    Json(json!({ "status": "ok" })).into_response()
+}
+
+/// Ready once all content parses and the static asset directories exist, and until shutdown.
+pub async fn readyz(State(options): State<LeptosOptions>) -> Response {
+    if crate::shutdown::is_shutting_down() {
+        return (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(json!({ "status": "shutting down" })),
+        )
+            .into_response();
+    }
+    let mut errors = content_errors().await;
+    let pkg = Path::new(options.site_root.as_ref()).join(options.site_pkg_dir.as_ref());
+    for dir in [Path::new("public"), pkg.as_path()] {
+        if !dir.is_dir() {
+            errors.push(format!("missing directory {}", dir.display()));
+        }
+    }
+
+    if errors.is_empty() {
+        Json(json!({ "status": "ready" })).into_response()
+    } else {
+        (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(json!({ "status": "not ready", "errors": errors })),
+        )
+            .into_response()
+    }
+}
+
+/// Build information and a hash of the content currently served.
+pub async fn version(State(options): State<LeptosOptions>) -> Response {
+    let index = content_index().await;
+    let build_time = env!("BUILD_TIMESTAMP")
+        .parse()
+        .ok()
+        .and_then(|secs| chrono::DateTime::from_timestamp(secs, 0))
+        .map(|time| time.to_rfc3339());
+    Json(json!({
+        "version": env!("CARGO_PKG_VERSION"),
+        "commit": env!("GIT_COMMIT"),
+        "build_time": build_time,
+        "leptos_env": format!("{:?}", options.env),
+        "content_hash": index.hash,
+        "projects": index.projects,
+    }))
+    .into_response()
+}
@@ -0,0 +1,485 @@
+use crate::collections::{Collection, Entry};
+use crate::error_template::{error_template, AppError};
+use crate::locale::{format_date, use_locale};
+use chrono::NaiveDate;
+use leptos::prelude::*;
+use leptos::server_fn::codec::{Cbor, GetUrl};
+use leptos_meta::{Meta, Title};
+use leptos_router::components::A;
+use leptos_router::hooks::use_params_map;
+use serde::{Deserialize, Serialize};
+
+/// A line of the diff a revision made to the markdown.
+#[derive(Serialize, Deserialize, Clone)]
+pub enum DiffLine {
+    /// The start of a hunk, at this line of the new file.
+    Hunk(usize),
+    Context(String),
+    Added(String),
+    Removed(String),
+}
+
+/// A commit that changed an entry file.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Revision {
+    pub(crate) commit: String,
+    pub(crate) date: NaiveDate,
+    pub(crate) author: String,
+    pub(crate) summary: String,
+    /// Empty when the commit only renamed the file.
+    pub(crate) diff: Vec<DiffLine>,
+}
+
+#[server(endpoint = "history", input = GetUrl, output = Cbor)]
+pub async fn get_history(
+    collection: Collection,
+    slug: String,
+) -> Result<(Entry, Vec<Revision>), AppError> {
+    let (entry, _) = crate::collections::ssr::read_entry(collection, &slug)
+        .await
+        .inspect_err(AppError::set_response_status)?;
+    crate::caching::cache_server_fn::<GetHistory>().await;
+    let path = crate::collections::ssr::entry_path(collection, &slug);
+    Ok((entry, ssr::revisions(&path).await))
+}
+
+/// Reads the history of the content from [`ssr::FILE`] when it has been exported, otherwise
+/// from the git repository in the working directory.
+///
+/// Without either there is no history, the dates fall back to the file system.
+#[cfg(feature = "ssr")]
+pub mod ssr {
+    use super::{DiffLine, Revision};
+    use crate::collections::{ssr::entry_files, Collection};
+    use chrono::NaiveDate;
+    use serde::{Deserialize, Serialize};
+    use std::collections::HashMap;
+    use std::path::{Path, PathBuf};
+    use std::sync::{Arc, LazyLock, Mutex, OnceLock};
+
+    /// The history exported by `website history`, so it can be served without the repository.
+    pub const FILE: &str = "./history.json";
+    /// Revisions listed on a history page, the oldest ones are left out.
+    const MAX_REVISIONS: usize = 50;
+
+    /// The dates of the first and last commits that changed a file.
+    #[derive(Serialize, Deserialize, Clone, Copy)]
+    pub struct CommitDates {
+        pub first: NaiveDate,
+        pub last: NaiveDate,
+    }
+
+    /// What has been read from git, valid while HEAD stays at `head`.
+    #[derive(Default)]
+    struct Cache {
+        head: String,
+        dates: HashMap<PathBuf, Arc<HashMap<PathBuf, CommitDates>>>,
+        revisions: HashMap<PathBuf, Arc<Vec<Revision>>>,
+    }
+
+    static CACHE: LazyLock<Mutex<Cache>> = LazyLock::new(Default::default);
+
+    /// The contents of [`FILE`], everything the content is read with at the exported HEAD.
+    #[derive(Serialize, Deserialize)]
+    struct Export {
+        head: String,
+        dates: HashMap<PathBuf, HashMap<PathBuf, CommitDates>>,
+        revisions: HashMap<PathBuf, Vec<Revision>>,
+    }
+
+    /// The exported history, read once. It is used instead of git when present.
+    fn exported() -> Option<&'static Cache> {
+        static EXPORTED: OnceLock<Option<Cache>> = OnceLock::new();
+        EXPORTED
+            .get_or_init(|| {
+                let json = std::fs::read(FILE).ok()?;
+                let export = serde_json::from_slice::<Export>(&json)
+                    .inspect_err(|e| tracing::warn!("ignoring invalid {}: {}", FILE, e))
+                    .ok()?;
+                Some(Cache {
+                    head: export.head,
+                    dates: export
+                        .dates
+                        .into_iter()
+                        .map(|(k, v)| (k, Arc::new(v)))
+                        .collect(),
+                    revisions: export
+                        .revisions
+                        .into_iter()
+                        .map(|(k, v)| (k, Arc::new(v)))
+                        .collect(),
+                })
+            })
+            .as_ref()
+    }
+
+    /// Reads the history of every collection from git and writes it to [`FILE`].
+    pub async fn export() -> Result<(), String> {
+        let head = head().ok_or("not in a git repository")?;
+        let mut dates = HashMap::new();
+        let mut revisions = HashMap::new();
+        for collection in Collection::ALL {
+            let dir = Path::new(collection.config().dir);
+            dates.insert(dir.to_path_buf(), read_commit_dates(dir).await);
+            for (_, path) in entry_files(collection) {
+                revisions.insert(path.clone(), read_revisions(&path).await);
+            }
+        }
+        let export = Export {
+            head,
+            dates,
+            revisions,
+        };
+        let json = serde_json::to_vec(&export).map_err(|e| e.to_string())?;
+        std::fs::write(FILE, json).map_err(|e| format!("writing {}: {}", FILE, e))
+    }
+
+    /// The git directory of the working directory, looked up once.
+    fn git_dir() -> Option<&'static Path> {
+        static GIT_DIR: OnceLock<Option<PathBuf>> = OnceLock::new();
+        GIT_DIR
+            .get_or_init(|| {
+                std::process::Command::new("git")
+                    .args(["rev-parse", "--absolute-git-dir"])
+                    .output()
+                    .ok()
+                    .filter(|output| output.status.success())
+                    .and_then(|output| String::from_utf8(output.stdout).ok())
+                    .map(|dir| PathBuf::from(dir.trim()))
+            })
+            .as_deref()
+    }
+
+    /// The commit HEAD points at, read from the git directory without running git, or the one
+    /// the history was exported at.
+    pub fn head() -> Option<String> {
+        let Some(git_dir) = git_dir() else {
+            return exported().map(|exported| exported.head.clone());
+        };
+        let head = std::fs::read_to_string(git_dir.join("HEAD")).ok()?;
+        let Some(reference) = head.strip_prefix("ref:").map(str::trim) else {
+            return Some(head.trim().to_string());
+        };
+        if let Ok(commit) = std::fs::read_to_string(git_dir.join(reference)) {
+            return Some(commit.trim().to_string());
+        }
+        std::fs::read_to_string(git_dir.join("packed-refs"))
+            .ok()?
+            .lines()
+            .find_map(|line| {
+                let (commit, name) = line.split_once(' ')?;
+                (name == reference).then(|| commit.to_string())
+            })
+    }
+
+    /// The value cached for `key`, or `read` when there is none for the current HEAD.
+    async fn cached<T, F>(
+        key: &Path,
+        field: fn(&mut Cache) -> &mut HashMap<PathBuf, Arc<T>>,
+        read: F,
+    ) -> Option<Arc<T>>
+    where
+        F: std::future::Future<Output = T>,
+    {
+        let head = head()?;
+        {
+            let mut cache = CACHE.lock().unwrap_or_else(|e| e.into_inner());
+            if cache.head != head {
+                *cache = Cache {
+                    head: head.clone(),
+                    ..Default::default()
+                };
+            }
+            if let Some(value) = field(&mut cache).get(key) {
+                return Some(value.clone());
+            }
+        }
+        let value = Arc::new(read.await);
+        let mut cache = CACHE.lock().unwrap_or_else(|e| e.into_inner());
+        if cache.head == head {
+            field(&mut cache).insert(key.to_path_buf(), value.clone());
+        }
+        Some(value)
+    }
+
+    async fn git(args: &[&str]) -> Option<String> {
+        let output = tokio::process::Command::new("git")
+            .args(args)
+            .output()
+            .await;
+        match output {
+            Ok(output) if output.status.success() => String::from_utf8(output.stdout).ok(),
+            Ok(output) => {
+                tracing::debug!(
+                    "git {}: {}",
+                    args.join(" "),
+                    String::from_utf8_lossy(&output.stderr).trim()
+                );
+                None
+            }
+            Err(e) => {
+                tracing::debug!("running git: {}", e);
+                None
+            }
+        }
+    }
+
+    /// The commit dates of the files under `path`, keyed by their current path relative to the
+    /// working directory. Renamed files keep the dates of their earlier names.
+    pub async fn commit_dates(path: &Path) -> Arc<HashMap<PathBuf, CommitDates>> {
+        if let Some(exported) = exported() {
+            return exported.dates.get(path).cloned().unwrap_or_default();
+        }
+        cached(path, |cache| &mut cache.dates, read_commit_dates(path))
+            .await
+            .unwrap_or_default()
+    }
+
+    async fn read_commit_dates(path: &Path) -> HashMap<PathBuf, CommitDates> {
+        let mut dates = HashMap::new();
+        let path = path.to_string_lossy();
+        let Some(log) = git(&[
+            "log",
+            "--format=%x1e%cs",
+            "--name-status",
+            "--find-renames",
+            "--relative",
+            "--",
+            &path,
+        ])
+        .await
+        else {
+            return dates;
+        };
+        // The log is newest first, so a rename is seen before the commits of the earlier name
+        let mut renamed = HashMap::<String, String>::new();
+        for commit in log.split('\x1e') {
+            let mut lines = commit.lines();
+            let Some(date) = lines.next().and_then(|date| date.parse::<NaiveDate>().ok()) else {
+                continue;
+            };
+            for line in lines {
+                let mut fields = line.split('\t');
+                let (Some(status), Some(file)) = (fields.next(), fields.next()) else {
+                    continue;
+                };
+                let current = match (status.starts_with('R'), fields.next()) {
+                    (true, Some(new)) => {
+                        let current = renamed.get(new).cloned().unwrap_or(new.to_string());
+                        renamed.insert(file.to_string(), current.clone());
+                        current
+                    }
+                    _ => renamed.get(file).cloned().unwrap_or(file.to_string()),
+                };
+                dates
+                    .entry(PathBuf::from(current))
+                    .and_modify(|dates: &mut CommitDates| {
+                        dates.first = dates.first.min(date);
+                        dates.last = dates.last.max(date);
+                    })
+                    .or_insert(CommitDates {
+                        first: date,
+                        last: date,
+                    });
+            }
+        }
+        dates
+    }
+
+    /// The commits that changed the file at `path` with their patches, newest first. Renames are
+    /// followed.
+    pub async fn revisions(path: &Path) -> Vec<Revision> {
+        if let Some(exported) = exported() {
+            return exported
+                .revisions
+                .get(path)
+                .map(|revisions| revisions.to_vec())
+                .unwrap_or_default();
+        }
+        cached(path, |cache| &mut cache.revisions, read_revisions(path))
+            .await
+            .map(|revisions| revisions.to_vec())
+            .unwrap_or_default()
+    }
+
+    async fn read_revisions(path: &Path) -> Vec<Revision> {
+        let max_count = format!("--max-count={}", MAX_REVISIONS);
+        let path = path.to_string_lossy();
+        let Some(log) = git(&[
+            "log",
+            "--follow",
+            "--patch",
+            "--no-color",
+            "--no-ext-diff",
+            &max_count,
+            "--format=%x1e%H%x1f%cs%x1f%an%x1f%s",
+            "--",
+            &path,
+        ])
+        .await
+        else {
+            return Vec::new();
+        };
+        log.split('\x1e').filter_map(parse_revision).collect()
+    }
+
+    /// Parses a commit of `git log --patch`, the formatted header line is followed by the patch.
+    fn parse_revision(commit: &str) -> Option<Revision> {
+        let (header, patch) = commit.split_once('\n').unwrap_or((commit, ""));
+        let mut fields = header.split('\x1f');
+        let commit = fields.next()?.to_string();
+        let date = fields.next()?.parse().ok()?;
+        let author = fields.next()?.to_string();
+        let summary = fields.next()?.to_string();
+
+        let mut diff = Vec::new();
+        let mut in_hunk = false;
+        for line in patch.lines() {
+            if line.starts_with("diff ") {
+                in_hunk = false;
+            } else if let Some(range) = line.strip_prefix("@@ ") {
+                // `@@ -1,4 +1,5 @@`, the start of the new range
+                let start = range
+                    .split_whitespace()
+                    .find_map(|range| range.strip_prefix('+'))
+                    .and_then(|range| range.split(',').next()?.parse().ok())
+                    .unwrap_or(1);
+                diff.push(DiffLine::Hunk(start));
+                in_hunk = true;
+            } else if in_hunk {
+                if let Some(text) = line.strip_prefix('+') {
+                    diff.push(DiffLine::Added(text.to_string()));
+                } else if let Some(text) = line.strip_prefix('-') {
+                    diff.push(DiffLine::Removed(text.to_string()));
+                } else if let Some(text) = line.strip_prefix(' ') {
+                    diff.push(DiffLine::Context(text.to_string()));
+                }
+            }
+        }
+        Some(Revision {
+            commit,
+            date,
+            author,
+            summary,
+            diff,
+        })
+    }
+}
+
+/// When an entry was first published and last updated, with a link to its history.
+#[component]
+pub fn LastUpdated(entry: Entry) -> impl IntoView {
+    let locale = use_locale();
+    let updated = entry.updated.unwrap_or(entry.date);
+    view! {
+        <p class="text-sm opacity-75 mt-6">
+            {entry
+                .published
+                .map(|published| format!("First published {} · ", format_date(published, locale)))}
+            "Last updated "
+            <time datetime=updated.to_string()>{format_date(updated, locale)}</time>
+            " · "
+            <A href=format!("{}/history", entry.url) {..} class="underline">"History"</A>
+        </p>
+    }
+}
+
+/// Lists the commits that changed an entry with their diffs, the slug is taken from the `id`
+/// route parameter.
+#[component]
+pub fn History(collection: Collection) -> impl IntoView {
+    let params = use_params_map();
+    let id = move || params.with(|params| params.get("id")).unwrap();
+    let resource = Resource::new_blocking(id, move |slug| async move {
+        get_history(collection, slug).await
+    });
+
+    view! {
+        <div class="m-auto md:w-3/5 w-full max-md:m-2 flex flex-col text-gray-700 dark:text-gray-400 ">
+            <Suspense>
+                {move || match resource.get() {
+                    None => view! { <p>"Loading..."</p> }.into_any(),
+                    Some(data) => {
+                        view! {
+                            <ErrorBoundary fallback=error_template>
+                                {data.map(|(entry, revisions)| {
+                                    let title = format!("History of {}", entry.name);
+                                    view! {
+                                        <Title text=format!("Lukas Hermansson - {}", &title)/>
+                                        <Meta property="og:title" content=title.clone()/>
+                                        <Meta property="og:type" content="website"/>
+                                        <h1 class="text-4xl mt-3 font-bold">{title}</h1>
+                                        <p class="opacity-75 mb-3">
+                                            <A href=entry.url {..} class="underline">
+                                                {format!("Back to the {}", collection.config().item_label)}
+                                            </A>
+                                        </p>
+                                        {if revisions.is_empty() {
+                                            view! { <p>"No history is available."</p> }.into_any()
+                                        } else {
+                                            revisions
+                                                .into_iter()
+                                                .map(|revision| view! { <RevisionView revision/> })
+                                                .collect::<Vec<_>>()
+                                                .into_any()
+                                        }}
+                                    }
+                                })}
+                            </ErrorBoundary>
+                        }
+                            .into_any()
+                    }
+                }}
+
+            </Suspense>
+        </div>
+    }
+}
+
+#[component]
+fn RevisionView(revision: Revision) -> impl IntoView {
+    let date = format_date(revision.date, use_locale());
+    let short = revision
+        .commit
+        .get(..7)
+        .unwrap_or(&revision.commit)
+        .to_string();
+    view! {
+        <section class="my-4">
+            <h2 class="text-xl font-bold">{revision.summary}</h2>
+            <p class="text-sm opacity-75">
+                <time datetime=revision.date.to_string()>{date}</time>
+                {format!(" · {} · ", revision.author)}
+                <code title=revision.commit>{short}</code>
+            </p>
+            {if revision.diff.is_empty() {
+                view! { <p class="text-sm italic">"Renamed, the text did not change."</p> }.into_any()
+            } else {
+                view! {
+                    <pre class="overflow-x-auto text-sm rounded bg-slate-100 dark:bg-slate-900 p-2 mt-1">
+                        {revision
+                            .diff
+                            .into_iter()
+                            .map(|line| {
+                                let (class, text) = match line {
+                                    DiffLine::Hunk(start) => {
+                                        ("block italic opacity-75", format!("Line {}", start))
+                                    }
+                                    DiffLine::Context(text) => ("block", format!(" {}", text)),
+                                    DiffLine::Added(text) => {
+                                        ("block bg-green-100 dark:bg-green-950", format!("+{}", text))
+                                    }
+                                    DiffLine::Removed(text) => {
+                                        ("block bg-red-100 dark:bg-red-950", format!("-{}", text))
+                                    }
+                                };
+                                view! { <span class=class>{text}</span> }
+                            })
+                            .collect::<Vec<_>>()}
+                    </pre>
+                }
+                    .into_any()
+            }}
+        </section>
+    }
+}
@@ -0,0 +1,392 @@
+use axum::{
+    body::{Body, Bytes},
+    extract::{MatchedPath, Request, State},
+    http::{header, StatusCode},
+    middleware::Next,
+    response::Response,
+};
+use futures::StreamExt;
+use leptos::config::LeptosOptions;
+
+/// Elements whose content is copied as is.
+const RAW_ELEMENTS: &[&str] = &["pre", "textarea", "script", "style"];
+/// Attributes that only restate the default.
+const REDUNDANT_ATTRIBUTES: &[&str] = &[
+    " type=\"text/javascript\"",
+    " type=\"text/css\"",
+    " class=\"\"",
+];
+/// Html kept from the start of a page to learn its critical css from.
+const SAMPLE: usize = 64 * 1024;
+
+enum Context {
+    Text,
+    /// Inside a tag, it is collected and rewritten once complete.
+    Tag {
+        quote: Option<u8>,
+    },
+    Comment,
+    /// Inside a raw element, `matched` bytes of its closing tag have been seen.
+    Raw {
+        close: Vec<u8>,
+        matched: usize,
+    },
+    /// Inside the closing tag of a raw element.
+    RawEnd,
+}
+
+/// Minifies html as it streams through, a chunk boundary can fall anywhere.
+///
+/// Whitespace is collapsed rather than removed so the text nodes the islands hydrate stay the
+/// same. The comments Leptos uses as hydration and streaming markers never start with
+/// whitespace, the ones that do are removed.
+pub struct Minifier {
+    context: Context,
+    tag: Vec<u8>,
+    /// Whitespace not written yet, a newline if the run had one.
+    space: Option<u8>,
+}
+
+impl Default for Minifier {
+    fn default() -> Self {
+        Minifier {
+            context: Context::Text,
+            tag: Vec::new(),
+            space: None,
+        }
+    }
+}
+
+impl Minifier {
+    pub fn push(&mut self, chunk: &[u8], out: &mut Vec<u8>) {
+        for &b in chunk {
+            match &mut self.context {
+                Context::Text if b.is_ascii_whitespace() => {
+                    self.space = Some(if b == b'\n' || self.space == Some(b'\n') {
+                        b'\n'
+                    } else {
+                        b' '
+                    });
+                }
+                Context::Text if b == b'<' => {
+                    self.tag.clear();
+                    self.tag.push(b);
+                    self.context = Context::Tag { quote: None };
+                }
+                Context::Text => {
+                    out.extend(self.space.take());
+                    out.push(b);
+                }
+                Context::Tag { quote: Some(q) } => {
+                    if b == *q {
+                        self.context = Context::Tag { quote: None };
+                    }
+                    self.tag.push(b);
+                }
+                Context::Tag { quote: None } => match b {
+                    b'"' | b'\'' => {
+                        self.tag.push(b);
+                        self.context = Context::Tag { quote: Some(b) };
+                    }
+                    b'>' => {
+                        if self.tag.last() == Some(&b' ') {
+                            self.tag.pop();
+                        }
+                        self.tag.push(b);
+                        self.end_tag(out);
+                    }
+                    _ if b.is_ascii_whitespace() => {
+                        if self.tag.last() != Some(&b' ') {
+                            self.tag.push(b' ');
+                        }
+                    }
+                    _ => {
+                        self.tag.push(b);
+                        if self.tag == b"<!--" {
+                            self.context = Context::Comment;
+                        }
+                    }
+                },
+                Context::Comment => {
+                    self.tag.push(b);
+                    if self.tag.len() >= 7 && self.tag.ends_with(b"-->") {
+                        // A removed comment leaves the whitespace before it pending, it merges
+                        // with the whitespace after it
+                        if !self.tag[4].is_ascii_whitespace() {
+                            out.extend(self.space.take());
+                            out.extend_from_slice(&self.tag);
+                        }
+                        self.context = Context::Text;
+                    }
+                }
+                Context::Raw { close, matched } => {
+                    out.push(b);
+                    if b.to_ascii_lowercase() == close[*matched] {
+                        *matched += 1;
+                        if *matched == close.len() {
+                            self.context = Context::RawEnd;
+                        }
+                    } else {
+                        *matched = usize::from(b == b'<');
+                    }
+                }
+                Context::RawEnd => {
+                    out.push(b);
+                    if b == b'>' {
+                        self.context = Context::Text;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Writes what is left at the end of the document.
+    pub fn finish(&mut self, out: &mut Vec<u8>) {
+        out.extend(self.space.take());
+        if matches!(self.context, Context::Tag { .. } | Context::Comment) {
+            out.append(&mut self.tag);
+        }
+        self.context = Context::Text;
+    }
+
+    /// Writes the complete tag and enters the raw elements.
+    fn end_tag(&mut self, out: &mut Vec<u8>) {
+        out.extend(self.space.take());
+        strip_redundant(&self.tag, out);
+        let name = self.tag[1..]
+            .split(|b| b.is_ascii_whitespace() || *b == b'>' || *b == b'/')
+            .next()
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        self.context = match RAW_ELEMENTS.iter().find(|raw| raw.as_bytes() == name) {
+            Some(raw) => Context::Raw {
+                close: format!("</{}", raw).into_bytes(),
+                matched: 0,
+            },
+            None => Context::Text,
+        };
+    }
+}
+
+/// Writes a complete tag without its [`REDUNDANT_ATTRIBUTES`], only whole attributes outside
+/// of quotes are removed.
+fn strip_redundant(tag: &[u8], out: &mut Vec<u8>) {
+    let mut quote = None;
+    let mut i = 0;
+    while i < tag.len() {
+        match (tag[i], quote) {
+            (q, Some(open)) if q == open => quote = None,
+            (_, Some(_)) => {}
+            (q @ (b'"' | b'\''), None) => quote = Some(q),
+            (b' ', None) => {
+                let redundant = REDUNDANT_ATTRIBUTES.iter().find(|attribute| {
+                    tag[i..].starts_with(attribute.as_bytes())
+                        && matches!(tag.get(i + attribute.len()), Some(b' ' | b'>' | b'/'))
+                });
+                if let Some(attribute) = redundant {
+                    i += attribute.len();
+                    continue;
+                }
+            }
+            _ => {}
+        }
+        out.push(tag[i]);
+        i += 1;
+    }
+}
+
+/// Minifies a response body and keeps its start for learning the critical css of `route`.
+struct Optimizer {
+    minifier: Minifier,
+    options: LeptosOptions,
+    route: Option<String>,
+    sample: Vec<u8>,
+}
+
+impl Optimizer {
+    fn chunk(&mut self, chunk: &[u8]) -> Bytes {
+        let mut out = Vec::with_capacity(chunk.len());
+        self.minifier.push(chunk, &mut out);
+        self.record(&out);
+        out.into()
+    }
+
+    fn finish(&mut self) -> Bytes {
+        let mut out = Vec::new();
+        self.minifier.finish(&mut out);
+        self.record(&out);
+        if let Some(route) = &self.route {
+            crate::critical_css::learn(&self.options, route, &self.sample);
+        }
+        out.into()
+    }
+
+    fn record(&mut self, out: &[u8]) {
+        if self.route.is_some() {
+            let take = out.len().min(SAMPLE - self.sample.len());
+            self.sample.extend_from_slice(&out[..take]);
+        }
+    }
+}
+
+/// Minifies html responses as they stream and learns the critical css of their route.
+pub async fn optimize(State(options): State<LeptosOptions>, req: Request, next: Next) -> Response {
+    let route = req
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_string());
+    let response = next.run(req).await;
+    let is_html = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("text/html"));
+    if !is_html {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    parts.headers.remove(header::CONTENT_LENGTH);
+    let optimizer = Optimizer {
+        minifier: Minifier::default(),
+        options,
+        // Error pages look nothing like the pages of their route
+        route: route.filter(|_| parts.status == StatusCode::OK),
+        sample: Vec::new(),
+    };
+    let stream = futures::stream::unfold(
+        (body.into_data_stream(), Some(optimizer)),
+        |(mut chunks, optimizer)| async move {
+            let mut optimizer = optimizer?;
+            match chunks.next().await {
+                Some(Ok(chunk)) => Some((Ok(optimizer.chunk(&chunk)), (chunks, Some(optimizer)))),
+                Some(Err(e)) => Some((Err(e), (chunks, None))),
+                None => Some((Ok(optimizer.finish()), (chunks, None))),
+            }
+        },
+    );
+    // The compression layer polls the body again after its end
+    Response::from_parts(parts, Body::from_stream(stream.fuse()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Minifies `html` in one chunk, split at every byte, and one byte at a time, the results
+    /// have to be the same.
+    fn minify(html: &str) -> String {
+        let minify_chunks = |chunks: &[&[u8]]| {
+            let mut minifier = Minifier::default();
+            let mut out = Vec::new();
+            for chunk in chunks {
+                minifier.push(chunk, &mut out);
+            }
+            minifier.finish(&mut out);
+            String::from_utf8(out).unwrap()
+        };
+        let bytes = html.as_bytes();
+        let whole = minify_chunks(&[bytes]);
+        for split in 1..bytes.len() {
+            let (a, b) = bytes.split_at(split);
+            assert_eq!(minify_chunks(&[a, b]), whole, "split at {}", split);
+        }
+        let single = bytes.chunks(1).collect::<Vec<_>>();
+        assert_eq!(minify_chunks(&single), whole, "split at every byte");
+        whole
+    }
+
+    #[test]
+    fn collapses_whitespace() {
+        assert_eq!(minify("<p>a   b\t c</p>"), "<p>a b c</p>");
+        assert_eq!(
+            minify("<p>a \n  b</p>\n\n<p>c</p>"),
+            "<p>a\nb</p>\n<p>c</p>"
+        );
+        assert_eq!(
+            minify("<a  href=\"/\"\n  class=\"x\" >a</a>"),
+            "<a href=\"/\" class=\"x\">a</a>"
+        );
+    }
+
+    #[test]
+    fn keeps_whitespace_inside_attribute_values() {
+        assert_eq!(
+            minify("<p title=\"a  >  b\">c</p>"),
+            "<p title=\"a  >  b\">c</p>"
+        );
+        assert_eq!(
+            minify("<p title='a \"  b'>c</p>"),
+            "<p title='a \"  b'>c</p>"
+        );
+    }
+
+    #[test]
+    fn keeps_hydration_markers() {
+        let html = "<div><!--hk=0-0-0--><!>a <!----> b<!--/--></div>";
+        assert_eq!(minify(html), html);
+        assert_eq!(
+            minify("<div><!--<() />-->a<!--leptos-view|x-->b</div>"),
+            "<div><!--<() />-->a<!--leptos-view|x-->b</div>"
+        );
+    }
+
+    #[test]
+    fn removes_comments_starting_with_whitespace() {
+        assert_eq!(minify("<p>a <!-- note --> b</p>"), "<p>a b</p>");
+        assert_eq!(minify("<p>a<!--\n> -- <p> -->b</p>"), "<p>ab</p>");
+        assert_eq!(minify("<p><!-- <!--x--> -->a</p>"), "<p> -->a</p>");
+    }
+
+    #[test]
+    fn copies_raw_elements() {
+        for html in [
+            "<pre>a\n   b  <b>c</b></pre>",
+            "<PRE class=\"x\">  a  </PRE>",
+            "<textarea>  a\n\n  b </textarea>",
+            "<script>if (a  <  b) { x = \"</p>  \" }</script>",
+            "<style>a  >  b { color: red }</style>",
+            "<script>a </scrip  t> </script >",
+        ] {
+            assert_eq!(minify(html), html);
+        }
+        assert_eq!(
+            minify("<pre> a </pre>  <p> b </p>"),
+            "<pre> a </pre> <p> b </p>"
+        );
+        assert_eq!(minify("<preview>  a  </preview>"), "<preview> a </preview>");
+    }
+
+    #[test]
+    fn removes_redundant_attributes() {
+        assert_eq!(
+            minify("<script type=\"text/javascript\" src=\"/a.js\"></script>"),
+            "<script src=\"/a.js\"></script>"
+        );
+        assert_eq!(minify("<link type=\"text/css\"/>"), "<link/>");
+        assert_eq!(minify("<p class=\"\">a</p>"), "<p>a</p>");
+        assert_eq!(
+            minify("<p\n  class=\"\"  id=\"a\">a</p>"),
+            "<p id=\"a\">a</p>"
+        );
+    }
+
+    #[test]
+    fn keeps_redundant_attributes_text_inside_values() {
+        for html in [
+            "<p title=\"x class=&quot;&quot; y\">a</p>",
+            "<p title='a type=\"text/css\" b'>a</p>",
+            "<p data-class=\"\">a</p>",
+            "<p class=\"\"x>a</p>",
+        ] {
+            assert_eq!(minify(html), html);
+        }
+    }
+
+    #[test]
+    fn writes_unfinished_input() {
+        assert_eq!(minify("a <p title=\"b"), "a <p title=\"b");
+        assert_eq!(minify("a <!-- b"), "a <!-- b");
+        assert_eq!(minify("a  "), "a ");
+    }
+}
@@ -0,0 +1,281 @@
+use axum::{
+    extract::Path,
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+};
+use image::{imageops::FilterType, DynamicImage, ImageFormat};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::path::{Path as FsPath, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{LazyLock, Mutex};
+use std::time::SystemTime;
+
+/// Widths the responsive variants are generated at, images are never upscaled.
+const WIDTHS: &[u32] = &[480, 960, 1440];
+const PLACEHOLDER_WIDTH: u32 = 16;
+const CACHE_DIR: &str = "./cache/images";
+/// Bump when the variant generation changes so stale variants are regenerated.
+const PIPELINE_VERSION: &str = "1";
+const SIZES: &str = "(min-width: 768px) 60vw, 100vw";
+
+/// Processed source images by path, valid while their modification time stays the same.
+static PROCESSED: LazyLock<Mutex<HashMap<PathBuf, (SystemTime, ProcessedImage)>>> =
+    LazyLock::new(Default::default);
+
+#[derive(Clone)]
+struct ProcessedImage {
+    width: u32,
+    height: u32,
+    placeholder: String,
+    variants: Vec<u32>,
+    hash: String,
+}
+
+impl ProcessedImage {
+    fn srcset(&self, extension: &str) -> String {
+        self.variants
+            .iter()
+            .map(|w| format!("/images/{}-{}.{} {}w", self.hash, w, extension, w))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+/// Serves the generated variants, they are keyed by content hash and therefore immutable.
+pub async fn image_handler(Path(file): Path<String>) -> Response {
+    if file.contains('/') || file.contains("..") {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+    let content_type = match file.rsplit_once('.').map(|(_, ext)| ext) {
+        Some("webp") => "image/webp",
+        Some("avif") => "image/avif",
+        _ => return StatusCode::NOT_FOUND.into_response(),
+    };
+    match tokio::fs::read(format!("{}/{}", CACHE_DIR, file)).await {
+        Ok(bytes) => (
+            [
+                (header::CONTENT_TYPE, content_type),
+                (header::CACHE_CONTROL, "public, max-age=31536000, immutable"),
+            ],
+            bytes,
+        )
+            .into_response(),
+        Err(_) => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+/// Rewrites every `<img>` in rendered content into a `<picture>` with responsive variants,
+/// relative sources are read from `dir`, the directory of the content.
+///
+/// Images that can't be resolved to a local raster file are kept as is, only made lazy.
+pub async fn rewrite_images(html: &str, dir: &std::path::Path) -> String {
+    let mut output = String::with_capacity(html.len());
+    let mut rest = html;
+    while let Some(start) = rest.find("<img ") {
+        output.push_str(&rest[..start]);
+        let Some(end) = rest[start..].find('>') else {
+            break;
+        };
+        let tag = &rest[start..start + end + 1];
+        rest = &rest[start + end + 1..];
+
+        let attributes = parse_attributes(tag);
+        let src = attribute(&attributes, "src").unwrap_or_default();
+        let processed = match source_path(src, dir) {
+            Some(path) => tokio::task::spawn_blocking(move || process(path))
+                .await
+                .ok()
+                .flatten(),
+            None => None,
+        };
+        output.push_str(&picture(&attributes, processed.as_ref()));
+    }
+    output.push_str(rest);
+    output
+}
+
+fn parse_attributes(tag: &str) -> Vec<(String, String)> {
+    let mut attributes = Vec::new();
+    let mut rest = tag.trim_start_matches("<img").trim_end_matches('>');
+    while let Some(eq) = rest.find("=\"") {
+        let name = rest[..eq].trim().to_string();
+        let Some(len) = rest[eq + 2..].find('"') else {
+            break;
+        };
+        attributes.push((name, rest[eq + 2..eq + 2 + len].to_string()));
+        rest = &rest[eq + 2 + len + 1..];
+    }
+    attributes
+}
+
+fn attribute<'a>(attributes: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    attributes
+        .iter()
+        .find(|(n, _)| n == name)
+        .map(|(_, v)| v.as_str())
+}
+
+/// Resolves an image reference in content to a file on disk.
+///
+/// `/assets/` maps to the `public` directory and relative paths to `dir`.
+fn source_path(src: &str, dir: &std::path::Path) -> Option<PathBuf> {
+    let src = src.replace("&amp;", "&");
+    if src.contains("..") || src.contains("://") || src.starts_with("data:") {
+        return None;
+    }
+    let path = match src.strip_prefix("/assets/") {
+        Some(asset) => PathBuf::from("./public").join(asset),
+        None if !src.starts_with('/') => dir.join(src),
+        None => return None,
+    };
+    let raster = matches!(
+        path.extension()
+            .and_then(|e| e.to_str())
+            .map(str::to_lowercase)
+            .as_deref(),
+        Some("jpg" | "jpeg" | "png" | "webp" | "gif")
+    );
+    raster.then_some(path)
+}
+
+fn picture(attributes: &[(String, String)], processed: Option<&ProcessedImage>) -> String {
+    let mut img = String::from("<img");
+    for (name, value) in attributes {
+        img.push_str(&format!(" {}=\"{}\"", name, value));
+    }
+    img.push_str(" loading=\"lazy\" decoding=\"async\"");
+
+    let Some(processed) = processed else {
+        img.push_str(" />");
+        return img;
+    };
+    img.push_str(&format!(
+        " width=\"{}\" height=\"{}\" style=\"background-image:url({});background-size:cover\" />",
+        processed.width, processed.height, processed.placeholder
+    ));
+    format!(
+        "<picture><source type=\"image/avif\" srcset=\"{}\" sizes=\"{}\" /><source type=\"image/webp\" srcset=\"{}\" sizes=\"{}\" />{}</picture>",
+        processed.srcset("avif"),
+        SIZES,
+        processed.srcset("webp"),
+        SIZES,
+        img
+    )
+}
+
+/// The processed source image, only read again when it is modified.
+fn process(path: PathBuf) -> Option<ProcessedImage> {
+    let modified = std::fs::metadata(&path).and_then(|m| m.modified()).ok()?;
+    if let Some((cached_modified, processed)) = PROCESSED
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .get(&path)
+    {
+        if *cached_modified == modified {
+            return Some(processed.clone());
+        }
+    }
+    let processed = generate(&path)?;
+    PROCESSED
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .insert(path, (modified, processed.clone()));
+    Some(processed)
+}
+
+/// Writes `bytes` to a temporary file first, a concurrent request never sees a partial variant.
+fn store(path: &FsPath, bytes: &[u8]) -> std::io::Result<()> {
+    static NEXT: AtomicU64 = AtomicU64::new(0);
+    let temporary = path.with_extension(format!(
+        "{}-{}.tmp",
+        std::process::id(),
+        NEXT.fetch_add(1, Ordering::Relaxed)
+    ));
+    std::fs::write(&temporary, bytes)
+        .and_then(|_| std::fs::rename(&temporary, path))
+        .inspect_err(|_| {
+            let _ = std::fs::remove_file(&temporary);
+        })
+}
+
+fn encode(image: &DynamicImage, format: ImageFormat) -> Option<Vec<u8>> {
+    let mut bytes = Vec::new();
+    image
+        .write_to(&mut std::io::Cursor::new(&mut bytes), format)
+        .ok()?;
+    Some(bytes)
+}
+
+/// Generates the variants of a source image unless they are already in the disk cache.
+fn generate(path: &FsPath) -> Option<ProcessedImage> {
+    let source = std::fs::read(path).ok()?;
+    let mut hasher = Sha256::new();
+    hasher.update(PIPELINE_VERSION);
+    hasher.update(&source);
+    let hash = format!("{:x}", hasher.finalize());
+    let cached = |name: &str| PathBuf::from(CACHE_DIR).join(name);
+
+    let (width, height) = image::ImageReader::new(std::io::Cursor::new(&source))
+        .with_guessed_format()
+        .ok()?
+        .into_dimensions()
+        .ok()?;
+    let mut variants = WIDTHS
+        .iter()
+        .copied()
+        .filter(|w| *w < width)
+        .collect::<Vec<_>>();
+    if variants.len() < WIDTHS.len() {
+        variants.push(width);
+    }
+
+    let placeholder_path = cached(&format!("{}-placeholder.png", hash));
+    let complete = placeholder_path.exists()
+        && variants.iter().all(|w| {
+            cached(&format!("{}-{}.webp", hash, w)).exists()
+                && cached(&format!("{}-{}.avif", hash, w)).exists()
+        });
+    if !complete {
+        tracing::debug!("generating image variants for {}", path.display());
+        let image = image::load_from_memory(&source)
+            .inspect_err(|err| tracing::warn!("could not decode {}: {}", path.display(), err))
+            .ok()?;
+        std::fs::create_dir_all(CACHE_DIR).ok()?;
+        for w in &variants {
+            let resized = resize(&image, *w);
+            // The webp encoder only supports 8 bit rgb(a)
+            let rgba = DynamicImage::ImageRgba8(resized.to_rgba8());
+            let webp = encode(&rgba, ImageFormat::WebP)?;
+            store(&cached(&format!("{}-{}.webp", hash, w)), &webp).ok()?;
+            let mut avif = Vec::new();
+            let encoder =
+                image::codecs::avif::AvifEncoder::new_with_speed_quality(&mut avif, 10, 70);
+            rgba.write_with_encoder(encoder).ok()?;
+            store(&cached(&format!("{}-{}.avif", hash, w)), &avif).ok()?;
+        }
+        let placeholder = encode(
+            &resize(&image, PLACEHOLDER_WIDTH).blur(1.5),
+            ImageFormat::Png,
+        )?;
+        store(&placeholder_path, &placeholder).ok()?;
+    }
+
+    use base64::Engine;
+    let placeholder = format!(
+        "data:image/png;base64,{}",
+        base64::engine::general_purpose::STANDARD.encode(std::fs::read(placeholder_path).ok()?)
+    );
+    Some(ProcessedImage {
+        width,
+        height,
+        placeholder,
+        variants,
+        hash,
+    })
+}
+
+fn resize(image: &DynamicImage, width: u32) -> DynamicImage {
+    let height = (image.height() as u64 * width as u64 / image.width().max(1) as u64).max(1);
+    image.resize_exact(width, height as u32, FilterType::Lanczos3)
+}
@@ -1,12 +1,62 @@
+#[cfg(feature = "ssr")]
+pub mod api;
 pub mod app;
+pub mod blog;
+pub mod bundle;
+#[cfg(feature = "ssr")]
+pub mod caching;
+pub mod collections;
+#[cfg(feature = "ssr")]
+pub mod critical_css;
 pub mod error_template;
+#[cfg(feature = "ssr")]
+pub mod health;
+#[cfg(feature = "ssr")]
+pub mod feed;
+pub mod history;
+#[cfg(feature = "ssr")]
+pub mod html;
+#[cfg(feature = "ssr")]
+pub mod images;
+pub mod lifecycle;
+pub mod locale;
+#[cfg(feature = "ssr")]
+pub mod logging;
+#[cfg(feature = "ssr")]
+pub mod metrics;
 pub mod navbar;
+#[cfg(feature = "ssr")]
+pub mod og;
+pub mod pages;
 pub mod projects;
+#[cfg(feature = "ssr")]
+pub mod rate_limit;
+#[cfg(feature = "ssr")]
+pub mod redirects;
+#[cfg(feature = "ssr")]
+pub mod security;
+#[cfg(feature = "ssr")]
+pub mod service_worker;
+#[cfg(feature = "ssr")]
+pub mod shutdown;
+#[cfg(feature = "ssr")]
+pub mod sitemap;
+pub mod theme;
+#[cfg(feature = "ssr")]
+pub mod tls;
+
+pub const SITE_URL: &str = "https://lukashermansson.me";
 
 #[cfg(feature = "hydrate")]
 #[wasm_bindgen::prelude::wasm_bindgen]
 pub fn hydrate() {
-    use crate::app::*;
     console_error_panic_hook::set_once();
-    leptos::mount::hydrate_body(App);
+    // Only the `#[island]` components are hydrated, the rest of the page is server rendered
+    leptos::mount::hydrate_islands();
+
+    // Browsers without service worker support leave `serviceWorker` undefined
+    let navigator = leptos::prelude::window().navigator();
+    if js_sys::Reflect::has(&navigator, &"serviceWorker".into()).unwrap_or(false) {
+        let _ = navigator.service_worker().register("/sw.js");
+    }
 }
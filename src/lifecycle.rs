@@ -0,0 +1,244 @@
+use crate::locale::{format_date, format_month, use_locale};
+use chrono::{Datelike, Locale, NaiveDate};
+use leptos::prelude::*;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+use std::str::FromStr;
+
+/// A front matter date that may only give the year or the month, like `2022` or `2022-02`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PartialDate {
+    Year(i32),
+    Month(i32, u32),
+    Day(NaiveDate),
+}
+
+impl PartialDate {
+    /// The months since year 0 of its first month, or of its last month for the end of a period.
+    fn month_index(self, end: bool) -> i32 {
+        let (year, month) = match self {
+            PartialDate::Year(year) => (year, if end { 12 } else { 1 }),
+            PartialDate::Month(year, month) => (year, month),
+            PartialDate::Day(date) => (date.year(), date.month()),
+        };
+        year * 12 + month as i32 - 1
+    }
+
+    fn format(self, locale: Locale) -> String {
+        match self {
+            PartialDate::Year(year) => year.to_string(),
+            PartialDate::Month(year, month) => NaiveDate::from_ymd_opt(year, month, 1)
+                .map(|date| format_month(date, locale))
+                .unwrap_or_else(|| self.to_string()),
+            PartialDate::Day(date) => format_date(date, locale),
+        }
+    }
+}
+
+impl fmt::Display for PartialDate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PartialDate::Year(year) => write!(f, "{:04}", year),
+            PartialDate::Month(year, month) => write!(f, "{:04}-{:02}", year, month),
+            PartialDate::Day(date) => write!(f, "{}", date),
+        }
+    }
+}
+
+impl FromStr for PartialDate {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid date {:?}, expected YYYY, YYYY-MM or YYYY-MM-DD", s);
+        let parts = s.trim().split('-').collect::<Vec<_>>();
+        match parts[..] {
+            [year] => year.parse().map(PartialDate::Year).map_err(|_| invalid()),
+            [year, month] => {
+                let year = year.parse().map_err(|_| invalid())?;
+                let month = month.parse().map_err(|_| invalid())?;
+                NaiveDate::from_ymd_opt(year, month, 1)
+                    .map(|_| PartialDate::Month(year, month))
+                    .ok_or_else(invalid)
+            }
+            _ => s
+                .trim()
+                .parse()
+                .map(PartialDate::Day)
+                .map_err(|_| invalid()),
+        }
+    }
+}
+
+impl Serialize for PartialDate {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for PartialDate {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        // A bare year in yaml is a number
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Raw {
+            Year(i32),
+            Text(String),
+        }
+        match Raw::deserialize(deserializer)? {
+            Raw::Year(year) => Ok(PartialDate::Year(year)),
+            Raw::Text(text) => text.parse().map_err(serde::de::Error::custom),
+        }
+    }
+}
+
+/// Where a project is in its life.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    Active,
+    Maintained,
+    Archived,
+    Superseded,
+}
+
+impl Status {
+    pub const ALL: [Status; 4] = [
+        Status::Active,
+        Status::Maintained,
+        Status::Archived,
+        Status::Superseded,
+    ];
+
+    /// The value used in the front matter and the `status` query parameter.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Status::Active => "active",
+            Status::Maintained => "maintained",
+            Status::Archived => "archived",
+            Status::Superseded => "superseded",
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Status::Active => "Active",
+            Status::Maintained => "Maintained",
+            Status::Archived => "Archived",
+            Status::Superseded => "Superseded",
+        }
+    }
+
+    /// Whether work on the project has ended.
+    fn ended(self) -> bool {
+        matches!(self, Status::Archived | Status::Superseded)
+    }
+
+    fn badge_class(self) -> &'static str {
+        match self {
+            Status::Active => "bg-green-100 text-green-800 dark:bg-green-950 dark:text-green-300",
+            Status::Maintained => "bg-sky-100 text-sky-800 dark:bg-sky-950 dark:text-sky-300",
+            Status::Archived => "bg-gray-200 text-gray-700 dark:bg-gray-700 dark:text-gray-300",
+            Status::Superseded => {
+                "bg-amber-100 text-amber-800 dark:bg-amber-950 dark:text-amber-300"
+            }
+        }
+    }
+}
+
+impl FromStr for Status {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Status::ALL
+            .into_iter()
+            .find(|status| status.as_str() == s)
+            .ok_or(())
+    }
+}
+
+/// When a project was worked on and its status, every part is optional.
+#[derive(Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub struct Lifecycle {
+    pub(crate) start: Option<PartialDate>,
+    pub(crate) end: Option<PartialDate>,
+    pub(crate) status: Option<Status>,
+}
+
+impl Lifecycle {
+    /// A period without an end is ongoing, unless the status says the work has ended.
+    fn ongoing(&self) -> bool {
+        self.end.is_none() && !self.status.is_some_and(Status::ended)
+    }
+
+    /// The period, like "2022 – present" or "February 2022 – March 2023".
+    pub fn period(&self, locale: Locale) -> Option<String> {
+        let start = self.start?.format(locale);
+        Some(match self.end {
+            Some(end) if Some(end) == self.start => start,
+            Some(end) => format!("{} – {}", start, end.format(locale)),
+            None if self.ongoing() => format!("{} – present", start),
+            None => start,
+        })
+    }
+
+    /// How long the project has run until `today`, only as precise as its dates.
+    pub fn duration(&self, today: NaiveDate) -> Option<String> {
+        let start = self.start?;
+        let end = match self.end {
+            Some(end) => end,
+            None if self.ongoing() => PartialDate::Day(today),
+            None => return None,
+        };
+        let months = (end.month_index(true) - start.month_index(false) + 1).max(1);
+        let in_years = [start, end]
+            .iter()
+            .any(|date| matches!(date, PartialDate::Year(_)));
+        Some(if in_years {
+            plural((months / 12).max(1), "year")
+        } else {
+            match (months / 12, months % 12) {
+                (0, months) => plural(months, "month"),
+                (years, 0) => plural(years, "year"),
+                (years, months) => format!("{} {}", plural(years, "year"), plural(months, "month")),
+            }
+        })
+    }
+}
+
+fn plural(count: i32, unit: &str) -> String {
+    if count == 1 {
+        format!("1 {}", unit)
+    } else {
+        format!("{} {}s", count, unit)
+    }
+}
+
+#[component]
+pub fn StatusBadge(status: Status) -> impl IntoView {
+    view! {
+        <span class=format!("inline-block rounded px-1 text-xs font-semibold {}", status.badge_class())>
+            {status.label()}
+        </span>
+    }
+}
+
+/// The status badge, period and duration of a project, nothing when it has none of them.
+#[component]
+pub fn LifecycleLine(lifecycle: Lifecycle) -> impl IntoView {
+    let locale = use_locale();
+    let today = chrono::Utc::now().date_naive();
+    let period = lifecycle
+        .period(locale)
+        .map(|period| match lifecycle.duration(today) {
+            Some(duration) => format!("{} · {}", period, duration),
+            None => period,
+        });
+    (lifecycle.status.is_some() || period.is_some()).then(|| {
+        view! {
+            <p class="text-sm mt-1 flex flex-wrap items-center gap-2">
+                {lifecycle.status.map(|status| view! { <StatusBadge status/> })}
+                {period.map(|period| view! { <span class="opacity-75">{period}</span> })}
+            </p>
+        }
+    })
+}
@@ -0,0 +1,111 @@
+use chrono::{Locale, NaiveDate};
+
+/// Used when the request has no `Accept-Language` we have a locale for.
+const DEFAULT: Locale = Locale::en_US;
+
+/// The locale dates are formatted in, see [`ssr::provide_locale`].
+///
+/// The pages are in English either way, only the dates follow the reader.
+pub fn use_locale() -> Locale {
+    leptos::prelude::use_context::<Locale>().unwrap_or(DEFAULT)
+}
+
+/// A date written out the way `locale` writes dates, like "November 9, 2023" or "9 november 2023".
+pub fn format_date(date: NaiveDate, locale: Locale) -> String {
+    let name = format!("{:?}", locale);
+    let language = name.split('_').next().unwrap_or_default();
+    let pattern = match (language, name.as_str()) {
+        (_, "en_US" | "en_CA" | "en_PH") => "%B %-d, %Y",
+        ("de" | "da" | "nb" | "nn" | "fi" | "cs" | "sk" | "hr" | "sl" | "is", _) => "%-d. %B %Y",
+        ("hu", _) => "%Y. %B %-d.",
+        ("ja" | "zh", _) => "%Y年%-m月%-d日",
+        ("ko", _) => "%Y년 %-m월 %-d일",
+        _ => "%-d %B %Y",
+    };
+    date.format_localized(pattern, locale).to_string()
+}
+
+/// A month written out the way `locale` writes it, like "November 2023".
+pub fn format_month(date: NaiveDate, locale: Locale) -> String {
+    let name = format!("{:?}", locale);
+    let pattern = match name.split('_').next().unwrap_or_default() {
+        "hu" => "%Y. %B",
+        "ja" | "zh" => "%Y年%-m月",
+        "ko" => "%Y년 %-m월",
+        _ => "%B %Y",
+    };
+    date.format_localized(pattern, locale).to_string()
+}
+
+#[cfg(feature = "ssr")]
+pub mod ssr {
+    use super::DEFAULT;
+    use chrono::Locale;
+    use http::header::{ACCEPT_LANGUAGE, VARY};
+    use http::HeaderValue;
+    use leptos::prelude::{provide_context, use_context};
+
+    /// Locales of languages whose main region does not share their code.
+    const LANGUAGE_DEFAULTS: &[(&str, &str)] = &[
+        ("en", "en_US"),
+        ("sv", "sv_SE"),
+        ("da", "da_DK"),
+        ("nb", "nb_NO"),
+        ("no", "nb_NO"),
+        ("nn", "nn_NO"),
+        ("cs", "cs_CZ"),
+        ("el", "el_GR"),
+        ("ja", "ja_JP"),
+        ("ko", "ko_KR"),
+        ("uk", "uk_UA"),
+        ("zh", "zh_CN"),
+    ];
+
+    fn parse(tag: &str) -> Option<Locale> {
+        let tag = tag.replace('-', "_");
+        if let Ok(locale) = Locale::try_from(tag.as_str()) {
+            return Some(locale);
+        }
+        let language = tag.split('_').next()?.to_lowercase();
+        match LANGUAGE_DEFAULTS.iter().find(|(l, _)| *l == language) {
+            Some((_, locale)) => Locale::try_from(*locale).ok(),
+            None => {
+                Locale::try_from(format!("{}_{}", language, language.to_uppercase()).as_str()).ok()
+            }
+        }
+    }
+
+    /// The first locale in an `Accept-Language` header we can format dates for.
+    fn from_accept_language(header: &str) -> Option<Locale> {
+        let mut languages = header
+            .split(',')
+            .filter_map(|language| {
+                let mut parts = language.trim().split(';');
+                let tag = parts.next()?.trim();
+                let quality = parts
+                    .find_map(|p| p.trim().strip_prefix("q="))
+                    .and_then(|q| q.parse::<f32>().ok())
+                    .unwrap_or(1.0);
+                (quality > 0.0 && tag != "*").then_some((tag, quality))
+            })
+            .collect::<Vec<_>>();
+        languages.sort_by(|a, b| b.1.total_cmp(&a.1));
+        languages.into_iter().find_map(|(tag, _)| parse(tag))
+    }
+
+    /// Provides the locale of the request to the Leptos context, call it in the shell.
+    ///
+    /// It has to run before the response head is sent, the page now varies by language.
+    pub fn provide_locale() {
+        if let Some(response) = use_context::<leptos_axum::ResponseOptions>() {
+            response.append_header(VARY, HeaderValue::from_static("accept-language"));
+        }
+        let locale = use_context::<http::request::Parts>()
+            .and_then(|parts| {
+                let header = parts.headers.get(ACCEPT_LANGUAGE)?.to_str().ok()?;
+                from_accept_language(header)
+            })
+            .unwrap_or(DEFAULT);
+        provide_context(locale);
+    }
+}
@@ -0,0 +1,146 @@
+use any_spawner::{CustomExecutor, Executor, PinnedFuture, PinnedLocalFuture};
+use axum::{
+    body::Body,
+    extract::{ConnectInfo, Request, State},
+    http::{header, HeaderMap, HeaderName},
+    middleware::Next,
+    response::Response,
+};
+use std::io::Write;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use tracing::{Instrument, Span};
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
+
+/// `pretty` (the default) or `json`.
+const FORMAT_ENV: &str = "LOG_FORMAT";
+/// Directory the access log is written to, access logging is disabled when unset.
+const ACCESS_LOG_ENV: &str = "ACCESS_LOG_DIR";
+
+pub static REQUEST_ID: HeaderName = HeaderName::from_static("x-request-id");
+
+/// Sets up the tracing subscriber in the format selected by `LOG_FORMAT`.
+pub fn init() {
+    let filter = tracing_subscriber::EnvFilter::try_from_default_env().unwrap_or_else(|_| {
+        // axum logs rejections from built-in extractors with the `axum::rejection`
+        // target, at `TRACE` level. `axum::rejection=trace` enables showing those events
+        "website=debug,tower_http=debug,axum::rejection=trace".into()
+    });
+    let registry = tracing_subscriber::registry().with(filter);
+    match std::env::var(FORMAT_ENV).as_deref() {
+        Ok("json") => registry
+            .with(
+                tracing_subscriber::fmt::layer()
+                    .json()
+                    .with_current_span(true),
+            )
+            .init(),
+        _ => registry
+            .with(tracing_subscriber::fmt::layer().pretty())
+            .init(),
+    }
+    // must happen before leptos_axum sets up its own tokio executor
+    let _ = Executor::init_custom_executor(InstrumentedExecutor);
+}
+
+/// Spawns the tasks Leptos starts while rendering (resources, suspense) in the span of the
+/// request that started them, so their logs carry its request id too.
+struct InstrumentedExecutor;
+
+impl CustomExecutor for InstrumentedExecutor {
+    fn spawn(&self, fut: PinnedFuture<()>) {
+        tokio::spawn(fut.in_current_span());
+    }
+
+    fn spawn_local(&self, fut: PinnedLocalFuture<()>) {
+        tokio::task::spawn_local(fut.in_current_span());
+    }
+
+    fn poll_local(&self) {}
+}
+
+/// The span every request is handled in, everything logged while handling it, server functions
+/// included, carries the request id.
+pub fn make_span(req: &Request) -> Span {
+    let request_id = req
+        .headers()
+        .get(&REQUEST_ID)
+        .and_then(|id| id.to_str().ok())
+        .unwrap_or("-");
+    tracing::info_span!(
+        "request",
+        method = %req.method(),
+        uri = %req.uri(),
+        version = ?req.version(),
+        request_id,
+    )
+}
+
+/// A daily rotated access log in the Combined Log Format.
+pub struct AccessLog(Mutex<RollingFileAppender>);
+
+impl AccessLog {
+    /// Opens the access log configured by `ACCESS_LOG_DIR`, if any.
+    pub fn from_env() -> Option<Arc<AccessLog>> {
+        let dir = std::env::var(ACCESS_LOG_ENV).ok()?;
+        let appender = RollingFileAppender::builder()
+            .rotation(Rotation::DAILY)
+            .filename_prefix("access")
+            .filename_suffix("log")
+            .build(&dir)
+            .inspect_err(|e| tracing::error!("failed to open the access log in {}: {}", dir, e))
+            .ok()?;
+        Some(Arc::new(AccessLog(Mutex::new(appender))))
+    }
+
+    pub fn flush(&self) {
+        if let Ok(mut file) = self.0.lock() {
+            let _ = file.flush();
+        }
+    }
+
+    fn write(&self, line: String) {
+        if let Ok(mut file) = self.0.lock() {
+            if let Err(e) = writeln!(file, "{}", line) {
+                tracing::error!("failed to write the access log: {}", e);
+            }
+        }
+    }
+}
+
+fn quoted(headers: &HeaderMap, name: header::HeaderName) -> String {
+    match headers.get(name).and_then(|v| v.to_str().ok()) {
+        Some(value) => format!("\"{}\"", value.replace('"', "\\\"")),
+        None => "\"-\"".to_string(),
+    }
+}
+
+/// Writes a Combined Log Format line once the response body has been sent.
+pub async fn access_log(
+    State(log): State<Option<Arc<AccessLog>>>,
+    req: Request<Body>,
+    next: Next,
+) -> Response {
+    let Some(log) = log else {
+        return next.run(req).await;
+    };
+    let host = req
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| addr.ip().to_string())
+        .unwrap_or_else(|| "-".to_string());
+    let time = chrono::Local::now().format("%d/%b/%Y:%H:%M:%S %z");
+    let request = format!("{} {} {:?}", req.method(), req.uri(), req.version());
+    let referer = quoted(req.headers(), header::REFERER);
+    let user_agent = quoted(req.headers(), header::USER_AGENT);
+
+    let response = next.run(req).await;
+    let status = response.status().as_u16();
+    crate::metrics::on_body_end(response, move |bytes| {
+        log.write(format!(
+            "{} - - [{}] \"{}\" {} {} {} {}",
+            host, time, request, status, bytes, referer, user_agent
+        ));
+    })
+}
@@ -1,8 +1,12 @@
 #[cfg(feature = "ssr")]
 #[tokio::main]
 async fn main() {
+    use axum::extract::Path;
+    use axum::middleware;
+    use axum::routing::{get, post};
     use axum::Router;
     use leptos::logging::log;
+    use std::sync::Arc;
     use leptos::prelude::*;
     use leptos_axum::{generate_route_list, LeptosRoutes};
     use tower_http::compression::CompressionLayer;
@@ -10,19 +14,44 @@ async fn main() {
     use tower_http::services::ServeDir;
     use tower_http::services::ServeFile;
     use tower_http::trace::TraceLayer;
-    use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
+    use tower_http::request_id::{MakeRequestUuid, PropagateRequestIdLayer, SetRequestIdLayer};
     use website::app::*;
+    use website::collections::Collection;
+    use website::feed::feed;
+    use website::health;
+    use website::logging;
+    use website::og::og_image;
+    use website::rate_limit::{self, RateLimiter};
+    use website::redirects::{self, Redirects};
+    use website::security;
+    use website::tls::{self, TlsListener, TlsSettings};
+    use website::sitemap::sitemap;
 
-    tracing_subscriber::registry()
-        .with(
-            tracing_subscriber::EnvFilter::try_from_default_env().unwrap_or_else(|_| {
-                // axum logs rejections from built-in extractors with the `axum::rejection`
-                // target, at `TRACE` level. `axum::rejection=trace` enables showing those events
-                "website=debug,tower_http=debug,axum::rejection=trace".into()
-            }),
-        )
-        .with(tracing_subscriber::fmt::layer())
-        .init();
+    website::logging::init();
+
+    // `website validate` checks the content and the redirect rules without serving
+    if std::env::args().nth(1).as_deref() == Some("validate") {
+        let mut errors = health::content_errors().await;
+        match Redirects::load(redirects::FILE) {
+            Ok(redirects) => errors.extend(redirects.find_loops()),
+            Err(e) => errors.extend(e),
+        }
+        for error in &errors {
+            eprintln!("{}", error);
+        }
+        std::process::exit(if errors.is_empty() { 0 } else { 1 });
+    }
+    // `website history` exports the history of the content, the image is built without git
+    if std::env::args().nth(1).as_deref() == Some("history") {
+        if let Err(e) = website::history::ssr::export().await {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+    let redirects = Redirects::load(redirects::FILE)
+        .map(Arc::new)
+        .unwrap_or_else(|errors| panic!("invalid {}: {}", redirects::FILE, errors.join(", ")));
     // Setting get_configuration(None) means we'll be using cargo-leptos's env values
     // For deployment these variables are:
     // <https://github.com/leptos-rs/start-axum#executing-a-server-on-a-remote-machine-without-the-toolchain>
@@ -31,7 +60,7 @@ async fn main() {
     let conf = get_configuration(None).unwrap();
     let leptos_options = conf.leptos_options;
     let addr = leptos_options.site_addr;
-    let routes = generate_route_list(App);
+    let routes = website::pages::ssr::page_routes(generate_route_list(App));
 
     let compression_layer: CompressionLayer = CompressionLayer::new()
         .gzip(true)
@@ -40,30 +69,149 @@ async fn main() {
         .compress_when(DefaultPredicate::new());
 
     // build our application with a route
-    let app = Router::new()
+    let mut app = Router::new();
+    for collection in Collection::ALL {
+        app = app.route(&collection.feed_url(), get(move || feed(collection)));
+    }
+    let metrics_handle = website::metrics::install();
+    let access_log = logging::AccessLog::from_env();
+
+    // Probes are kept out of the traced and compressed routes below
+    let probes = Router::new()
+        .route(
+            "/metrics",
+            get(move || website::metrics::metrics(metrics_handle.clone())),
+        )
+        .route("/healthz", get(health::healthz))
+        .route("/readyz", get(health::readyz))
+        .route("/version", get(health::version))
+        .with_state(leptos_options.clone());
+
+    let app = app
+        .nest("/api/v1", website::api::router())
         .nest_service("/assets", ServeDir::new("public"))
-        .nest_service("/favicon.ico", ServeFile::new("public/favicon.ico"))
-        .nest_service(
-            "/manifest.json",
-            ServeFile::new_with_mime(
-                "public/manifest.json",
-                &"application/manifest+json".parse::<mime::Mime>().unwrap(),
-            ),
+        .route("/sitemap.xml", get(sitemap))
+        .route("/csp-report", post(security::csp_report))
+        .route("/sw.js", get(website::service_worker::service_worker))
+        .route(
+            "/og/{file}",
+            get(|Path(file): Path<String>| og_image(Collection::Projects, file)),
+        )
+        .route(
+            "/og/blog/{file}",
+            get(|Path(file): Path<String>| og_image(Collection::Blog, file)),
         )
+        .route("/images/{file}", get(website::images::image_handler))
+        .nest_service("/favicon.ico", ServeFile::new("public/favicon.ico"))
+        .route("/manifest.json", get(website::theme::ssr::manifest))
         .leptos_routes(&leptos_options, routes, {
             let leptos_options = leptos_options.clone();
             move || shell(leptos_options.clone())
         })
         .fallback(leptos_axum::file_and_error_handler(shell))
-        .with_state(leptos_options)
-        .layer(TraceLayer::new_for_http())
-        .layer(compression_layer);
+        .with_state(leptos_options.clone())
+        .layer(middleware::from_fn(website::caching::not_modified))
+        .layer(middleware::from_fn_with_state(
+            leptos_options.clone(),
+            website::html::optimize,
+        ))
+        // Before the Leptos routes so old urls never reach them
+        .layer(middleware::from_fn_with_state(
+            redirects,
+            redirects::redirect,
+        ))
+        .layer(middleware::from_fn_with_state(
+            RateLimiter::from_env(),
+            rate_limit::rate_limit,
+        ))
+        .layer(middleware::from_fn(website::metrics::track))
+        .layer(compression_layer)
+        .layer(middleware::from_fn(website::metrics::track_wire_size))
+        .layer(middleware::from_fn_with_state(
+            leptos_options.clone(),
+            website::bundle::ssr::preload_links,
+        ))
+        .layer(middleware::from_fn_with_state(
+            leptos_options.clone(),
+            security::security_headers,
+        ))
+        .layer(PropagateRequestIdLayer::new(logging::REQUEST_ID.clone()))
+        .layer(TraceLayer::new_for_http().make_span_with(logging::make_span))
+        .layer(middleware::from_fn_with_state(
+            access_log.clone(),
+            logging::access_log,
+        ))
+        // Keeps the id sent by a proxy, generates one otherwise
+        .layer(SetRequestIdLayer::new(
+            logging::REQUEST_ID.clone(),
+            MakeRequestUuid,
+        ))
+        .merge(probes);
+
+    match TlsSettings::from_env() {
+        Some(tls) => {
+            if let Some(redirect_addr) = tls.redirect_addr {
+                let listener = tokio::net::TcpListener::bind(redirect_addr).await.unwrap();
+                log!("redirecting http://{} to https", redirect_addr);
+                tokio::spawn(async move {
+                    axum::serve(listener, tls::redirect_router(addr.port()))
+                        .await
+                        .unwrap()
+                });
+            }
+            let listener = TlsListener::bind(addr, tls).await.unwrap();
+            log!("listening on https://{}", &addr);
+            serve(listener, app).await;
+        }
+        None => {
+            let listener = tokio::net::TcpListener::bind(&addr).await.unwrap();
+            log!("listening on http://{}", &addr);
+            serve(listener, app).await;
+        }
+    }
+
+    if let Some(access_log) = &access_log {
+        access_log.flush();
+    }
+    tracing::info!("shutdown complete");
+    let _ = std::io::Write::flush(&mut std::io::stdout());
+    // Dropping the runtime would wait for blocking tasks such as image encoding
+    std::process::exit(0);
+}
+
+/// Serves until a shutdown signal, then waits for the in-flight requests up to the drain timeout.
+#[cfg(feature = "ssr")]
+async fn serve<L>(listener: L, app: axum::Router)
+where
+    L: axum::serve::Listener<Addr = std::net::SocketAddr>,
+{
+    use axum::serve::ListenerExt;
+    use std::future::IntoFuture;
+    use website::shutdown;
+
+    let (draining, drain_started) = tokio::sync::oneshot::channel();
+    // Tapping gives any listener the `SocketAddr` connect info
+    let server = axum::serve(
+        listener.tap_io(|_| ()),
+        app.into_make_service_with_connect_info::<std::net::SocketAddr>(),
+    )
+    .with_graceful_shutdown(async move {
+        shutdown::signal().await;
+        let _ = draining.send(());
+    })
+    .into_future();
 
-    let listener = tokio::net::TcpListener::bind(&addr).await.unwrap();
-    log!("listening on http://{}", &addr);
-    axum::serve(listener, app.into_make_service())
-        .await
-        .unwrap();
+    let mut server = std::pin::pin!(server);
+    tokio::select! {
+        result = &mut server => result.unwrap(),
+        Ok(()) = drain_started => {
+            let timeout = shutdown::drain_timeout();
+            match tokio::time::timeout(timeout, &mut server).await {
+                Ok(result) => result.unwrap(),
+                Err(_) => tracing::warn!("requests still in flight after {:?}, exiting", timeout),
+            }
+        }
+    }
 }
 
 #[cfg(not(feature = "ssr"))]
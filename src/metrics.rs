@@ -0,0 +1,171 @@
+use crate::collections::Collection;
+use axum::{
+    body::{Body, HttpBody},
+    extract::{MatchedPath, Request},
+    http::header,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use futures::StreamExt;
+use metrics_exporter_prometheus::{Matcher, PrometheusBuilder, PrometheusHandle};
+use std::time::Instant;
+
+const LATENCY_BUCKETS: &[f64] = &[
+    0.001, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+const SIZE_BUCKETS: &[f64] = &[
+    256.0, 1024.0, 4096.0, 16384.0, 65536.0, 262144.0, 1048576.0, 4194304.0,
+];
+/// Path prefixes 404s are counted under, anything else is counted as `other`.
+const NOT_FOUND_PREFIXES: &[&str] = &["api", "assets", "images", "og", "pkg"];
+
+/// Installs the global recorder, the returned handle renders the scrape output.
+pub fn install() -> PrometheusHandle {
+    PrometheusBuilder::new()
+        .set_buckets_for_metric(
+            Matcher::Full("http_request_duration_seconds".to_string()),
+            LATENCY_BUCKETS,
+        )
+        .and_then(|builder| {
+            builder.set_buckets_for_metric(Matcher::Suffix("_bytes".to_string()), SIZE_BUCKETS)
+        })
+        .and_then(|builder| builder.install_recorder())
+        .expect("failed to install the metrics recorder")
+}
+
+/// Serves the metrics in the Prometheus text format.
+pub async fn metrics(handle: PrometheusHandle) -> Response {
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        handle.render(),
+    )
+        .into_response()
+}
+
+/// Directories served by nested services, these requests have no [`MatchedPath`].
+const STATIC_PREFIXES: &[&str] = &["/assets/", "/pkg/"];
+
+/// The matched route, server functions are reported by name without their hash suffix.
+fn route(req: &Request) -> String {
+    let Some(path) = req.extensions().get::<MatchedPath>() else {
+        let path = req.uri().path();
+        return STATIC_PREFIXES
+            .iter()
+            .find(|prefix| path.starts_with(*prefix))
+            .map(|prefix| format!("{}*", prefix))
+            .unwrap_or_else(|| "fallback".to_string());
+    };
+    let path = path.as_str();
+    match path.strip_prefix("/api/") {
+        Some(name) => format!(
+            "/api/{}",
+            name.trim_end_matches(|c: char| c.is_ascii_digit())
+        ),
+        None => path.to_string(),
+    }
+}
+
+fn not_found_prefix(path: &str) -> &'static str {
+    let segment = path.trim_start_matches('/').split('/').next().unwrap_or("");
+    Collection::ALL
+        .iter()
+        .map(|c| c.config().route_prefix)
+        .chain(NOT_FOUND_PREFIXES.iter().copied())
+        .find(|prefix| *prefix == segment)
+        .unwrap_or("other")
+}
+
+/// Calls `f` with the body size once the body has been sent, streamed bodies are counted chunk
+/// by chunk.
+pub(crate) fn on_body_end(response: Response, f: impl FnOnce(u64) + Send + 'static) -> Response {
+    if let Some(size) = response.body().size_hint().exact() {
+        f(size);
+        return response;
+    }
+    struct Counter<F: FnOnce(u64)> {
+        bytes: u64,
+        f: Option<F>,
+    }
+    impl<F: FnOnce(u64)> Counter<F> {
+        fn add(&mut self, bytes: usize) {
+            self.bytes += bytes as u64;
+        }
+    }
+    impl<F: FnOnce(u64)> Drop for Counter<F> {
+        fn drop(&mut self) {
+            if let Some(f) = self.f.take() {
+                f(self.bytes);
+            }
+        }
+    }
+    let mut counter = Counter {
+        bytes: 0,
+        f: Some(f),
+    };
+    let (parts, body) = response.into_parts();
+    let body = body.into_data_stream().map(move |chunk| {
+        if let Ok(chunk) = &chunk {
+            counter.add(chunk.len());
+        }
+        chunk
+    });
+    Response::from_parts(parts, Body::from_stream(body))
+}
+
+fn record_size(response: Response, histogram: metrics::Histogram) -> Response {
+    on_body_end(response, move |size| histogram.record(size as f64))
+}
+
+/// Counts requests, their latency and uncompressed response size per route.
+pub async fn track(req: Request, next: Next) -> Response {
+    let start = Instant::now();
+    let method = req.method().to_string();
+    let route = route(&req);
+    let path = req.uri().path().to_string();
+
+    let response = next.run(req).await;
+
+    let status = response.status();
+    metrics::counter!(
+        "http_requests_total",
+        "method" => method.clone(),
+        "route" => route.clone(),
+        "status" => status.as_u16().to_string()
+    )
+    .increment(1);
+    metrics::histogram!(
+        "http_request_duration_seconds",
+        "method" => method,
+        "route" => route.clone()
+    )
+    .record(start.elapsed().as_secs_f64());
+    if status == axum::http::StatusCode::NOT_FOUND {
+        metrics::counter!("http_not_found_total", "prefix" => not_found_prefix(&path)).increment(1);
+    }
+    record_size(
+        response,
+        metrics::histogram!("http_response_size_bytes", "route" => route),
+    )
+}
+
+/// Records the response size as sent, placed outside the compression layer.
+///
+/// Comparing its sum with `http_response_size_bytes` gives the compression ratio.
+pub async fn track_wire_size(req: Request, next: Next) -> Response {
+    let route = route(&req);
+    let response = next.run(req).await;
+    let encoding = response
+        .headers()
+        .get(header::CONTENT_ENCODING)
+        .and_then(|e| e.to_str().ok())
+        .unwrap_or("identity")
+        .to_string();
+    record_size(
+        response,
+        metrics::histogram!(
+            "http_response_wire_size_bytes",
+            "route" => route,
+            "encoding" => encoding
+        ),
+    )
+}
@@ -1,10 +1,63 @@
+use crate::collections::Collection;
+use crate::pages::get_navbar_pages;
+use crate::theme::ThemeToggle;
+use leptos::ev::KeyboardEvent;
+use leptos::html;
 use leptos::prelude::*;
+use wasm_bindgen::JsCast;
 use leptos_router::components::A;
 
+/// Entries shown before the collections and the markdown pages, as `(label, href)`.
+const NAV_ITEMS: &[(&str, &str)] = &[("Home", "/")];
+
+const LINK_CLASS: &str = "flex items-center text-gray-600 dark:text-gray-300 hover:text-gray-900 dark:hover:text-gray-100 m-4 font-bold aria-[current=page]:text-gray-900 dark:aria-[current=page]:text-gray-100 aria-[current=page]:underline underline-offset-8";
+
+#[derive(Clone)]
+struct NavItem {
+    label: String,
+    href: String,
+}
+
+/// The configured entries followed by one entry per collection.
+fn static_items() -> Vec<NavItem> {
+    NAV_ITEMS
+        .iter()
+        .map(|(label, href)| NavItem {
+            label: label.to_string(),
+            href: href.to_string(),
+        })
+        .chain(Collection::ALL.iter().map(|c| NavItem {
+            label: c.config().title.to_string(),
+            href: format!("/{}", c.config().route_prefix),
+        }))
+        .collect()
+}
+
 #[component]
 pub fn Navbar() -> impl IntoView {
+    let pages = Resource::new(|| (), |_| get_navbar_pages());
+    let items = move || {
+        let pages = pages
+            .get()
+            .and_then(Result::ok)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|page| NavItem {
+                label: page.title,
+                href: page.url,
+            });
+        static_items().into_iter().chain(pages).collect::<Vec<_>>()
+    };
+    let links = move || {
+        view! {
+            <Suspense fallback=|| static_items().into_iter().map(nav_link).collect_view()>
+                {move || items().into_iter().map(nav_link).collect_view()}
+            </Suspense>
+        }
+    };
+
     view! {
-        <nav class="flex grow align-center top-0 left-0 bg-slate-700">
+        <nav class="flex grow align-center top-0 left-0 bg-slate-200 dark:bg-slate-700 relative">
             <A href="/" {..} class="m-1">
                 <img
                     src="/assets/logo.webp"
@@ -14,22 +67,113 @@ pub fn Navbar() -> impl IntoView {
                     width="64"
                 />
             </A>
-            <div class="flex justify-end grow">
-                <A
-                    href="/"
-        {..}
-                     class="flex items-center text-gray-300 hover:text-gray-300 m-4 font-bold aria-current_page:text-gray-100"
-                >
-                    Home
-                </A>
-                <A
-                    href="/projects"
-    {..}
-                     class="flex items-center text-gray-300 hover:text-gray-300 m-4 font-bold aria-current_page:text-gray-100"
-                >
-                    Projects
-                </A>
+            <div class="hidden md:flex justify-end grow">{links()}</div>
+            <div class="flex max-md:ml-auto">
+                <ThemeToggle/>
+                <MobileMenu>{links()}</MobileMenu>
             </div>
         </nav>
     }
 }
+
+fn nav_link(item: NavItem) -> impl IntoView {
+    view! {
+        <A href=item.href {..} class=LINK_CLASS>
+            {item.label}
+        </A>
+    }
+}
+
+/// A collapsible menu for narrow screens.
+///
+/// It is a `<details>` element so it can be opened before the page is hydrated, after hydration
+/// the open state is controlled here to add `aria-expanded`, a focus trap and Escape to close.
+#[island]
+fn MobileMenu(children: Children) -> impl IntoView {
+    let (open, set_open) = signal(false);
+    let (hydrated, set_hydrated) = signal(false);
+    let menu = NodeRef::<html::Details>::new();
+    let toggle = NodeRef::<html::Summary>::new();
+
+    Effect::new(move |_| set_hydrated.set(true));
+
+    let on_keydown = move |ev: KeyboardEvent| {
+        if !open.get_untracked() {
+            return;
+        }
+        match ev.key().as_str() {
+            "Escape" => {
+                set_open.set(false);
+                if let Some(toggle) = toggle.get_untracked() {
+                    let _ = toggle.focus();
+                }
+            }
+            "Tab" => {
+                let Some(menu) = menu.get_untracked() else {
+                    return;
+                };
+                let Ok(focusable) = menu.query_selector_all("summary, a[href]") else {
+                    return;
+                };
+                let (Some(first), Some(last)) = (
+                    focusable.item(0),
+                    focusable.item(focusable.length().saturating_sub(1)),
+                ) else {
+                    return;
+                };
+                let active = document().active_element().map(|e| e.unchecked_into());
+                let wrap_to = if ev.shift_key() && active.as_ref() == Some(&first) {
+                    Some(last)
+                } else if !ev.shift_key() && active.as_ref() == Some(&last) {
+                    Some(first)
+                } else {
+                    None
+                };
+                if let Some(target) = wrap_to {
+                    ev.prevent_default();
+                    let _ = target.unchecked_into::<web_sys::HtmlElement>().focus();
+                }
+            }
+            _ => {}
+        }
+    };
+
+    view! {
+        <details
+            node_ref=menu
+            class="md:hidden self-center"
+            prop:open=open
+            on:keydown=on_keydown
+        >
+            <summary
+                node_ref=toggle
+                class="list-none cursor-pointer m-4 text-gray-600 dark:text-gray-300 hover:text-gray-900 dark:hover:text-gray-100"
+                aria-controls="mobile-menu"
+                aria-expanded=move || hydrated.get().then(|| open.get().to_string())
+                on:click=move |ev| {
+                    ev.prevent_default();
+                    set_open.update(|open| *open = !*open);
+                }
+            >
+                <span class="sr-only">"Menu"</span>
+                <svg
+                    class="w-8 h-8"
+                    xmlns="http://www.w3.org/2000/svg"
+                    viewBox="0 0 24 24"
+                    fill="none"
+                    stroke="currentColor"
+                    stroke-width="2"
+                    aria-hidden="true"
+                >
+                    <path d="M4 6h16M4 12h16M4 18h16"></path>
+                </svg>
+            </summary>
+            <div
+                id="mobile-menu"
+                class="absolute left-0 right-0 top-full z-10 flex flex-col bg-slate-200 dark:bg-slate-700 shadow-md shadow-gray-300 dark:shadow-gray-950"
+            >
+                {children()}
+            </div>
+        </details>
+    }
+}
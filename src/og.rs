@@ -0,0 +1,178 @@
+use crate::collections::{ssr::read_entry, Collection, Entry};
+use axum::{
+    body::Bytes,
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, OnceLock};
+
+const WIDTH: u32 = 1200;
+const HEIGHT: u32 = 630;
+const CACHE_DIR: &str = "./cache/og";
+/// Bump when the template changes so stale cards are not served from the cache.
+const TEMPLATE_VERSION: &str = "1";
+const LOGO: &[u8] = include_bytes!("../public/logo.webp");
+
+/// Renders the social card for an entry, `file` is the entry slug with a `.png` suffix.
+pub async fn og_image(collection: Collection, file: String) -> Response {
+    let Some(slug) = file.strip_suffix(".png") else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let (entry, _) = match read_entry(collection, slug).await {
+        Ok(entry) => entry,
+        Err(e) => return e.status_code().into_response(),
+    };
+    let hash = card_hash(&entry);
+
+    let png = match cached(&hash).await {
+        Some(png) => png,
+        None => {
+            let svg = card_svg(&entry);
+            let rendered = tokio::task::spawn_blocking(move || render(&svg))
+                .await
+                .ok()
+                .flatten();
+            let Some(png) = rendered else {
+                tracing::error!("failed to render og card for {}", slug);
+                return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+            };
+            store(&hash, png.clone()).await;
+            png
+        }
+    };
+
+    (
+        [
+            (header::CONTENT_TYPE, "image/png".to_string()),
+            (header::CACHE_CONTROL, "public, max-age=86400".to_string()),
+            (header::ETAG, format!("\"{}\"", hash)),
+        ],
+        png,
+    )
+        .into_response()
+}
+
+fn memory_cache() -> &'static Mutex<HashMap<String, Bytes>> {
+    static CACHE: OnceLock<Mutex<HashMap<String, Bytes>>> = OnceLock::new();
+    CACHE.get_or_init(Default::default)
+}
+
+async fn cached(hash: &str) -> Option<Bytes> {
+    if let Some(png) = memory_cache().lock().unwrap().get(hash) {
+        return Some(png.clone());
+    }
+    let png = Bytes::from(
+        tokio::fs::read(format!("{}/{}.png", CACHE_DIR, hash))
+            .await
+            .ok()?,
+    );
+    memory_cache()
+        .lock()
+        .unwrap()
+        .insert(hash.to_string(), png.clone());
+    Some(png)
+}
+
+async fn store(hash: &str, png: Bytes) {
+    memory_cache()
+        .lock()
+        .unwrap()
+        .insert(hash.to_string(), png.clone());
+    // The disk cache is best effort, a read-only filesystem only costs a re-render after restart
+    if tokio::fs::create_dir_all(CACHE_DIR).await.is_ok() {
+        if let Err(err) = tokio::fs::write(format!("{}/{}.png", CACHE_DIR, hash), &png[..]).await {
+            tracing::warn!("could not write og card to disk cache: {}", err);
+        }
+    }
+}
+
+/// Hash of everything that ends up on the card.
+fn card_hash(entry: &Entry) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(TEMPLATE_VERSION);
+    hasher.update(&entry.name);
+    hasher.update(entry.date.to_string());
+    for tag in &entry.tags {
+        hasher.update([0]);
+        hasher.update(tag);
+    }
+    hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn card_svg(entry: &Entry) -> String {
+    use base64::Engine;
+    let logo = base64::engine::general_purpose::STANDARD.encode(LOGO);
+
+    // There is no text measuring in the template, so the tag pills are laid out with an
+    // approximate glyph width and wrapped onto a new row when they would overflow.
+    let mut tags = String::new();
+    let (mut x, mut y) = (80.0, 470.0);
+    for tag in &entry.tags {
+        let width = tag.chars().count() as f32 * 16.0 + 40.0;
+        if x + width > (WIDTH - 80) as f32 {
+            x = 80.0;
+            y += 64.0;
+        }
+        tags.push_str(&format!(
+            r##"<rect x="{x}" y="{y}" width="{width}" height="48" rx="8" fill="#1d293d"/><text x="{tx}" y="{ty}" font-size="28" fill="#d1d5dc">{tag}</text>"##,
+            tx = x + 20.0,
+            ty = y + 34.0,
+            tag = escape(tag),
+        ));
+        x += width + 16.0;
+    }
+
+    format!(
+        r##"<svg xmlns="http://www.w3.org/2000/svg" xmlns:xlink="http://www.w3.org/1999/xlink" width="{WIDTH}" height="{HEIGHT}" viewBox="0 0 {WIDTH} {HEIGHT}" font-family="DejaVu Sans, sans-serif">
+<rect width="{WIDTH}" height="{HEIGHT}" fill="#101828"/>
+<rect width="{WIDTH}" height="16" fill="#314158"/>
+<clipPath id="logo"><circle cx="128" cy="128" r="48"/></clipPath>
+<image x="80" y="80" width="96" height="96" clip-path="url(#logo)" xlink:href="data:image/webp;base64,{logo}"/>
+<text x="200" y="140" font-size="36" fill="#99a1af">Lukas Hermansson</text>
+<text x="80" y="320" font-size="72" font-weight="bold" fill="#f3f4f6">{title}</text>
+<text x="80" y="400" font-size="36" font-style="italic" fill="#99a1af">{date}</text>
+{tags}
+</svg>"##,
+        title = escape(&entry.name),
+        date = entry.date,
+    )
+}
+
+fn fontdb() -> Arc<resvg::usvg::fontdb::Database> {
+    static FONTDB: OnceLock<Arc<resvg::usvg::fontdb::Database>> = OnceLock::new();
+    FONTDB
+        .get_or_init(|| {
+            let mut db = resvg::usvg::fontdb::Database::new();
+            db.load_system_fonts();
+            Arc::new(db)
+        })
+        .clone()
+}
+
+fn render(svg: &str) -> Option<Bytes> {
+    let options = resvg::usvg::Options {
+        fontdb: fontdb(),
+        ..Default::default()
+    };
+    let tree = resvg::usvg::Tree::from_str(svg, &options).ok()?;
+    let mut pixmap = resvg::tiny_skia::Pixmap::new(WIDTH, HEIGHT)?;
+    resvg::render(
+        &tree,
+        resvg::tiny_skia::Transform::default(),
+        &mut pixmap.as_mut(),
+    );
+    pixmap.encode_png().ok().map(Bytes::from)
+}
@@ -0,0 +1,220 @@
+use crate::error_template::{error_template, AppError};
+use leptos::prelude::*;
+use leptos_meta::{Meta, Title};
+use leptos_router::hooks::use_params_map;
+use serde::{Deserialize, Serialize};
+
+/// A standalone markdown page from the `pages` directory, routed at its path.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Page {
+    pub(crate) url: String,
+    pub(crate) title: String,
+    pub(crate) description: String,
+    /// Whether the page gets a navbar entry.
+    pub(crate) navbar: bool,
+    pub(crate) order: i32,
+}
+
+#[server()]
+pub async fn get_page(path: String) -> Result<(Page, String), AppError> {
+    let (page, content) = ssr::read_page(&path)
+        .await
+        .inspect_err(AppError::set_response_status)?;
+    let html =
+        crate::images::rewrite_images(&markdown::to_html(&content), &ssr::page_dir(&path)).await;
+    Ok((page, html))
+}
+
+#[server()]
+pub async fn get_navbar_pages() -> Result<Vec<Page>, AppError> {
+    Ok(ssr::read_pages()
+        .await
+        .into_iter()
+        .filter(|p| p.navbar)
+        .collect())
+}
+
+#[cfg(feature = "ssr")]
+pub mod ssr {
+    use super::Page;
+    use crate::error_template::AppError;
+    use leptos_axum::AxumRouteListing;
+    use serde::Deserialize;
+    use std::path::{Path, PathBuf};
+
+    const DIR: &str = "./pages";
+    const EXTENSION: &str = "md";
+
+    #[derive(Deserialize)]
+    struct PageFrontMatter {
+        title: String,
+        #[serde(default)]
+        description: String,
+        #[serde(default)]
+        navbar: bool,
+        #[serde(default)]
+        order: i32,
+    }
+
+    pub fn parse_page(url: String, content: &str) -> Result<(Page, String), AppError> {
+        let matter = gray_matter::Matter::<gray_matter::engine::YAML>::new();
+        let result = matter
+            .parse::<PageFrontMatter>(content)
+            .map_err(|e| AppError::ContentInvalid(e.to_string()))?;
+        let data = result
+            .data
+            .ok_or_else(|| AppError::ContentInvalid("missing front matter".to_string()))?;
+        Ok((
+            Page {
+                url,
+                title: data.title,
+                description: data.description,
+                navbar: data.navbar,
+                order: data.order,
+            },
+            result.content,
+        ))
+    }
+
+    /// Reads the page routed at `path`, e.g. `about` reads `pages/about.md`.
+    pub async fn read_page(path: &str) -> Result<(Page, String), AppError> {
+        let path = path.trim_matches('/');
+        if path.is_empty() || path.split('/').any(|s| s.is_empty() || s.starts_with('.')) {
+            return Err(AppError::NotFound);
+        }
+        let file = PathBuf::from(DIR).join(format!("{}.{}", path, EXTENSION));
+        let content = tokio::fs::read_to_string(&file).await.map_err(|e| match e.kind() {
+            std::io::ErrorKind::NotFound => AppError::NotFound,
+            _ => AppError::Internal(format!("reading {}: {}", file.display(), e)),
+        })?;
+        parse_page(format!("/{}", path), &content).inspect_err(|e| {
+            tracing::warn!("invalid page {}: {}", file.display(), e);
+        })
+    }
+
+    /// The directory of the page routed at `path`, relative images are read from it.
+    pub fn page_dir(path: &str) -> PathBuf {
+        Path::new(DIR)
+            .join(path.trim_matches('/'))
+            .parent()
+            .map_or_else(|| PathBuf::from(DIR), Path::to_path_buf)
+    }
+
+    /// The page files as `(url, path)`, including the ones in sub directories.
+    pub fn page_files() -> Vec<(String, PathBuf)> {
+        let mut files = Vec::new();
+        collect_files(Path::new(DIR), &mut files);
+        files
+            .into_iter()
+            .filter_map(|file| {
+                let route = file
+                    .strip_prefix(DIR)
+                    .ok()?
+                    .with_extension("")
+                    .to_str()?
+                    .to_string();
+                Some((format!("/{}", route), file))
+            })
+            .collect()
+    }
+
+    /// Replaces the catch-all route of [`super::MarkdownPage`] with a route per page file, so
+    /// other paths still reach the static files. Pages added later are routed after a restart.
+    pub fn page_routes(routes: Vec<AxumRouteListing>) -> Vec<AxumRouteListing> {
+        let Some(catch_all) = routes.iter().find(|route| route.path().starts_with("/{*")) else {
+            return routes;
+        };
+        let pages = page_files()
+            .into_iter()
+            .map(|(url, _)| {
+                AxumRouteListing::new(
+                    url,
+                    catch_all.mode().clone(),
+                    catch_all.methods(),
+                    Vec::new(),
+                )
+            })
+            .collect::<Vec<_>>();
+        routes
+            .into_iter()
+            .filter(|route| !route.path().starts_with("/{*"))
+            .chain(pages)
+            .collect()
+    }
+
+    /// Reads every page, including the ones in sub directories, sorted by their order.
+    pub async fn read_pages() -> Vec<Page> {
+        metrics::counter!("content_index_reloads_total", "content" => "pages").increment(1);
+        let mut pages = Vec::new();
+        for (url, file) in page_files() {
+            match tokio::fs::read_to_string(&file)
+                .await
+                .map_err(|e| AppError::Internal(e.to_string()))
+                .and_then(|content| parse_page(url, &content))
+            {
+                Ok((page, _)) => pages.push(page),
+                Err(e) => {
+                    tracing::warn!("skipping invalid page {}: {}", file.display(), e);
+                    metrics::counter!("content_index_failures_total", "content" => "pages")
+                        .increment(1);
+                }
+            }
+        }
+        pages.sort_by(|a, b| a.order.cmp(&b.order).then_with(|| a.url.cmp(&b.url)));
+        pages
+    }
+
+    fn collect_files(dir: &Path, files: &mut Vec<PathBuf>) {
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            return;
+        };
+        for entry in entries.filter_map(|e| e.ok()) {
+            let path = entry.path();
+            if path.is_dir() {
+                collect_files(&path, files);
+            } else if path.extension().and_then(|e| e.to_str()) == Some(EXTENSION) {
+                files.push(path);
+            }
+        }
+    }
+}
+
+/// Renders the markdown page matching the current path, this is the catch-all route.
+#[component]
+pub fn MarkdownPage() -> impl IntoView {
+    let params = use_params_map();
+    let path = move || params.with(|params| params.get("path")).unwrap_or_default();
+    let resource = Resource::new_blocking(path, |arg| async move { get_page(arg).await });
+
+    view! {
+        <div class="m-auto md:w-3/5 w-full max-md:m-2 flex flex-col text-gray-700 dark:text-gray-400 ">
+            <Suspense>
+                {move || match resource.get() {
+                    None => view! { <p>"Loading..."</p> }.into_any(),
+                    Some(data) => {
+                        view! {
+                            <ErrorBoundary fallback=error_template>
+                                {data.map(|(page, data)| {
+                                    view! {
+                                        <Meta property="og:title" content=page.title.clone()/>
+                                        <Meta property="og:description" content=page.description/>
+                                        <Meta property="og:type" content="website"/>
+                                        <Meta
+                                            property="og:image"
+                                            content=format!("{}/assets/og-card.jpg", crate::SITE_URL)
+                                        />
+                                        <Title text=format!("Lukas Hermansson - {}", &page.title)/>
+                                        <h1 class="text-4xl my-3 font-bold">{page.title}</h1>
+                                        <div class="post" inner_html=data></div>
+                                    }
+                                })}
+                            </ErrorBoundary>
+                        }
+                            .into_any()
+                    }
+                }}
+
+            </Suspense>
+        </div>
+    }
+}
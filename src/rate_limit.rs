@@ -0,0 +1,348 @@
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    http::{header, HeaderMap, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+/// Comma separated addresses or CIDR ranges of the proxies whose `X-Forwarded-For` is trusted.
+const TRUSTED_PROXIES_ENV: &str = "TRUSTED_PROXIES";
+/// Buckets kept before the full ones are dropped, then the least recently used ones until
+/// [`LOW_BUCKETS`] remain, so cleanup runs at most once per thousand new clients.
+const MAX_BUCKETS: usize = 10_000;
+const LOW_BUCKETS: usize = 9_000;
+
+/// A group of routes sharing a limit, `(name, path prefix, burst, tokens per second)`.
+///
+/// The limits can be overridden with `RATE_LIMIT_<NAME>=<burst>:<per second>`.
+const GROUPS: &[(&str, &str, f64, f64)] = &[
+    // Server functions read from the filesystem on every call
+    ("api", "/api/", 30.0, 5.0),
+    // Cards are rendered on a cache miss
+    ("og", "/og/", 10.0, 1.0),
+];
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+struct Group {
+    name: &'static str,
+    prefix: &'static str,
+    burst: f64,
+    per_second: f64,
+    buckets: Mutex<HashMap<IpAddr, Bucket>>,
+}
+
+impl Group {
+    /// Takes a token for `ip`, or returns the seconds until one is available.
+    fn take(&self, ip: IpAddr) -> Result<(), u64> {
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock().unwrap_or_else(|e| e.into_inner());
+        if buckets.len() >= MAX_BUCKETS && !buckets.contains_key(&ip) {
+            let (burst, per_second) = (self.burst, self.per_second);
+            buckets.retain(|_, b| {
+                b.tokens + now.duration_since(b.updated).as_secs_f64() * per_second < burst
+            });
+            if buckets.len() > LOW_BUCKETS {
+                let mut updated = buckets.values().map(|b| b.updated).collect::<Vec<_>>();
+                let excess = buckets.len() - LOW_BUCKETS;
+                let (_, &mut cutoff, _) = updated.select_nth_unstable(excess - 1);
+                buckets.retain(|_, b| b.updated > cutoff);
+            }
+        }
+        let bucket = buckets.entry(ip).or_insert(Bucket {
+            tokens: self.burst,
+            updated: now,
+        });
+        let elapsed = now.duration_since(bucket.updated).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * self.per_second).min(self.burst);
+        bucket.updated = now;
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(((1.0 - bucket.tokens) / self.per_second).ceil() as u64)
+        }
+    }
+}
+
+/// A trusted proxy, an address with the number of leading bits that have to match.
+struct Network(IpAddr, u32);
+
+impl Network {
+    fn parse(value: &str) -> Option<Network> {
+        let (ip, bits) = match value.split_once('/') {
+            Some((ip, bits)) => (ip.parse::<IpAddr>().ok()?, Some(bits.parse().ok()?)),
+            None => (value.parse::<IpAddr>().ok()?, None),
+        };
+        let max = if ip.is_ipv4() { 32 } else { 128 };
+        let bits = bits.unwrap_or(max);
+        if bits > max {
+            return None;
+        }
+        // Addresses are compared in their canonical form, `::ffff:a.b.c.d` as `a.b.c.d`
+        match ip.to_canonical() {
+            IpAddr::V4(v4) if ip.is_ipv6() && bits >= 96 => Some(Network(v4.into(), bits - 96)),
+            _ => Some(Network(ip, bits)),
+        }
+    }
+
+    fn contains(&self, ip: IpAddr) -> bool {
+        let mask = |bits: u32, width: u32| match bits {
+            0 => 0,
+            bits => u128::MAX << (width - bits),
+        };
+        match (self.0, ip) {
+            (IpAddr::V4(net), IpAddr::V4(ip)) => {
+                let mask = mask(self.1, 32) as u32;
+                u32::from(net) & mask == u32::from(ip) & mask
+            }
+            (IpAddr::V6(net), IpAddr::V6(ip)) => {
+                let mask = mask(self.1, 128);
+                u128::from(net) & mask == u128::from(ip) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+pub struct RateLimiter {
+    groups: Vec<Group>,
+    trusted: Vec<Network>,
+}
+
+impl RateLimiter {
+    /// Builds the limiter from [`GROUPS`] and the environment overrides.
+    pub fn from_env() -> Arc<RateLimiter> {
+        let groups = GROUPS
+            .iter()
+            .map(|&(name, prefix, burst, per_second)| {
+                let var = format!("RATE_LIMIT_{}", name.to_uppercase());
+                let (burst, per_second) = std::env::var(&var)
+                    .ok()
+                    .and_then(|limit| {
+                        let (burst, per_second) = limit.split_once(':')?;
+                        Some((burst.parse().ok()?, per_second.parse().ok()?))
+                    })
+                    .filter(|&(burst, per_second): &(f64, f64)| burst >= 1.0 && per_second > 0.0)
+                    .unwrap_or((burst, per_second));
+                Group {
+                    name,
+                    prefix,
+                    burst,
+                    per_second,
+                    buckets: Mutex::new(HashMap::new()),
+                }
+            })
+            .collect();
+        let trusted = std::env::var(TRUSTED_PROXIES_ENV)
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|proxy| !proxy.is_empty())
+            .filter_map(|proxy| {
+                let network = Network::parse(proxy);
+                if network.is_none() {
+                    tracing::warn!("ignoring invalid trusted proxy {}", proxy);
+                }
+                network
+            })
+            .collect();
+        Arc::new(RateLimiter { groups, trusted })
+    }
+
+    fn is_trusted(&self, ip: IpAddr) -> bool {
+        self.trusted.iter().any(|network| network.contains(ip))
+    }
+
+    /// The client address, `X-Forwarded-For` is followed from the right for as long as the
+    /// hops are trusted proxies. IPv4-mapped IPv6 addresses are returned as IPv4.
+    fn client_ip(&self, peer: IpAddr, headers: &HeaderMap) -> IpAddr {
+        let peer = peer.to_canonical();
+        if !self.is_trusted(peer) {
+            return peer;
+        }
+        let mut client = peer;
+        for value in headers.get_all("x-forwarded-for").iter().rev() {
+            let Ok(value) = value.to_str() else {
+                return client;
+            };
+            for hop in value.rsplit(',') {
+                match hop.trim().parse::<IpAddr>() {
+                    Ok(ip) => client = ip.to_canonical(),
+                    Err(_) => return client,
+                }
+                if !self.is_trusted(client) {
+                    return client;
+                }
+            }
+        }
+        client
+    }
+}
+
+/// Answers with `429 Too Many Requests` once a client exhausts the bucket of a route group.
+pub async fn rate_limit(
+    State(limiter): State<Arc<RateLimiter>>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let path = req.uri().path();
+    let Some(group) = limiter.groups.iter().find(|g| path.starts_with(g.prefix)) else {
+        return next.run(req).await;
+    };
+    let Some(ConnectInfo(peer)) = req.extensions().get::<ConnectInfo<SocketAddr>>() else {
+        return next.run(req).await;
+    };
+    let client = limiter.client_ip(peer.ip(), req.headers());
+    match group.take(client) {
+        Ok(()) => next.run(req).await,
+        Err(retry_after) => {
+            tracing::warn!(group = group.name, client = %client, "rate limit exceeded");
+            (
+                StatusCode::TOO_MANY_REQUESTS,
+                [(header::RETRY_AFTER, retry_after.max(1).to_string())],
+            )
+                .into_response()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ip(ip: &str) -> IpAddr {
+        ip.parse().unwrap()
+    }
+
+    fn limiter(trusted: &[&str]) -> RateLimiter {
+        RateLimiter {
+            groups: Vec::new(),
+            trusted: trusted.iter().filter_map(|n| Network::parse(n)).collect(),
+        }
+    }
+
+    fn forwarded(values: &[&str]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for value in values {
+            headers.append("x-forwarded-for", value.parse().unwrap());
+        }
+        headers
+    }
+
+    fn group(burst: f64) -> Group {
+        Group {
+            name: "test",
+            prefix: "/",
+            burst,
+            per_second: 0.001,
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    #[test]
+    fn parses_networks() {
+        assert!(Network::parse("10.0.0.0/8").is_some());
+        assert!(Network::parse("10.0.0.1").is_some());
+        assert!(Network::parse("fd00::/8").is_some());
+        assert!(Network::parse("10.0.0.0/33").is_none());
+        assert!(Network::parse("fd00::/129").is_none());
+        assert!(Network::parse("10.0.0.0/x").is_none());
+        assert!(Network::parse("proxy").is_none());
+    }
+
+    #[test]
+    fn contains_addresses_in_the_network() {
+        let network = Network::parse("10.1.0.0/16").unwrap();
+        assert!(network.contains(ip("10.1.0.0")));
+        assert!(network.contains(ip("10.1.255.255")));
+        assert!(!network.contains(ip("10.2.0.0")));
+        assert!(!network.contains(ip("::ffff:10.1.0.1")));
+        let single = Network::parse("192.168.1.1").unwrap();
+        assert!(single.contains(ip("192.168.1.1")));
+        assert!(!single.contains(ip("192.168.1.2")));
+        let all = Network::parse("0.0.0.0/0").unwrap();
+        assert!(all.contains(ip("8.8.8.8")));
+        assert!(!all.contains(ip("::1")));
+        let v6 = Network::parse("fd00::/8").unwrap();
+        assert!(v6.contains(ip("fdab::1")));
+        assert!(!v6.contains(ip("fe80::1")));
+        let mapped = Network::parse("::ffff:10.1.0.0/112").unwrap();
+        assert!(mapped.contains(ip("10.1.2.3")));
+    }
+
+    #[test]
+    fn follows_forwarded_for_past_trusted_proxies() {
+        let limiter = limiter(&["10.0.0.0/8", "fd00::/8"]);
+        let headers = forwarded(&["1.1.1.1, 2.2.2.2, 10.0.0.2"]);
+        assert_eq!(limiter.client_ip(ip("10.0.0.1"), &headers), ip("2.2.2.2"));
+        // Every header is read, the last one is the closest hop
+        let headers = forwarded(&["1.1.1.1", "10.0.0.3, 10.0.0.2"]);
+        assert_eq!(limiter.client_ip(ip("fd00::1"), &headers), ip("1.1.1.1"));
+        let headers = forwarded(&["10.0.0.3"]);
+        assert_eq!(limiter.client_ip(ip("10.0.0.1"), &headers), ip("10.0.0.3"));
+    }
+
+    #[test]
+    fn ignores_forwarded_for_from_untrusted_peers() {
+        let limiter = limiter(&["10.0.0.0/8"]);
+        let headers = forwarded(&["1.1.1.1"]);
+        assert_eq!(limiter.client_ip(ip("3.3.3.3"), &headers), ip("3.3.3.3"));
+        assert_eq!(
+            limiter.client_ip(ip("10.0.0.1"), &HeaderMap::new()),
+            ip("10.0.0.1")
+        );
+        // An invalid hop stops the walk at the last valid address
+        let headers = forwarded(&["1.1.1.1, unknown, 10.0.0.2"]);
+        assert_eq!(limiter.client_ip(ip("10.0.0.1"), &headers), ip("10.0.0.2"));
+    }
+
+    #[test]
+    fn normalizes_mapped_addresses() {
+        let limiter = limiter(&["10.0.0.0/8"]);
+        let headers = forwarded(&["::ffff:1.1.1.1, ::ffff:10.0.0.2"]);
+        assert_eq!(
+            limiter.client_ip(ip("::ffff:10.0.0.1"), &headers),
+            ip("1.1.1.1")
+        );
+        assert_eq!(
+            limiter.client_ip(ip("::ffff:3.3.3.3"), &HeaderMap::new()),
+            ip("3.3.3.3")
+        );
+    }
+
+    #[test]
+    fn limits_each_client() {
+        let group = group(2.0);
+        assert!(group.take(ip("1.1.1.1")).is_ok());
+        assert!(group.take(ip("1.1.1.1")).is_ok());
+        assert!(group.take(ip("1.1.1.1")).is_err());
+        assert!(group.take(ip("2.2.2.2")).is_ok());
+    }
+
+    #[test]
+    fn evicts_the_least_recently_used_buckets() {
+        let group = group(10.0);
+        let first = ip("1.1.1.1");
+        for _ in 0..10 {
+            group.take(first).unwrap();
+        }
+        assert!(group.take(first).is_err());
+        for i in 0..MAX_BUCKETS as u32 * 3 {
+            group.take(IpAddr::V4((0x0b00_0000 + i).into())).unwrap();
+            assert!(group.buckets.lock().unwrap().len() <= MAX_BUCKETS);
+        }
+        let buckets = group.buckets.lock().unwrap();
+        assert!(!buckets.contains_key(&first));
+        assert!(buckets.contains_key(&IpAddr::V4(
+            (0x0b00_0000 + MAX_BUCKETS as u32 * 3 - 1).into()
+        )));
+    }
+}
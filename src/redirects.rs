@@ -0,0 +1,508 @@
+use axum::{
+    extract::{Request, State},
+    http::{header, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// The redirect rules, read once at startup.
+pub const FILE: &str = "./redirects.toml";
+/// Hops followed when checking the rules for loops.
+const MAX_HOPS: usize = 16;
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Default, Debug)]
+#[serde(rename_all = "lowercase")]
+enum MatchKind {
+    /// The path equals `from`.
+    #[default]
+    Exact,
+    /// The path is `from` or below it, the rest of it is appended to `to`. Prefixes match whole
+    /// segments, `/blog` does not match `/blogroll`.
+    Prefix,
+    /// `from` has `:name` segments matching one segment and a trailing `*name` matching the
+    /// rest, they are substituted into `to`.
+    Pattern,
+}
+
+fn default_status() -> u16 {
+    301
+}
+
+#[derive(Deserialize)]
+struct Rule {
+    from: String,
+    /// A path or an absolute url, not used for `410 Gone`.
+    to: Option<String>,
+    #[serde(default, rename = "match")]
+    kind: MatchKind,
+    #[serde(default = "default_status")]
+    status: u16,
+}
+
+#[derive(Deserialize)]
+struct RedirectsFile {
+    #[serde(default, rename = "redirect")]
+    rules: Vec<Rule>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Resolution {
+    Redirect(StatusCode, String),
+    Gone,
+}
+
+/// Captures the `:name` and `*name` segments of `pattern` if `path` matches it.
+fn match_pattern<'a>(pattern: &'a str, path: &'a str) -> Option<HashMap<&'a str, String>> {
+    let mut params = HashMap::new();
+    let mut segments = path.split('/');
+    for expected in pattern.split('/') {
+        if let Some(name) = expected.strip_prefix('*') {
+            params.insert(name, segments.collect::<Vec<_>>().join("/"));
+            return Some(params);
+        }
+        let segment = segments.next()?;
+        match expected.strip_prefix(':') {
+            Some(name) if !segment.is_empty() => {
+                params.insert(name, segment.to_string());
+            }
+            Some(_) => return None,
+            None if expected == segment => {}
+            None => return None,
+        }
+    }
+    segments.next().is_none().then_some(params)
+}
+
+fn substitute(to: &str, params: &HashMap<&str, String>) -> String {
+    to.split('/')
+        .map(|segment| {
+            segment
+                .strip_prefix(':')
+                .or_else(|| segment.strip_prefix('*'))
+                .and_then(|name| params.get(name).cloned())
+                .unwrap_or_else(|| segment.to_string())
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn param_names(pattern: &str) -> impl Iterator<Item = &str> {
+    pattern
+        .split('/')
+        .filter_map(|s| s.strip_prefix(':').or_else(|| s.strip_prefix('*')))
+}
+
+impl Rule {
+    /// The target of the rule for `path`, `Some(None)` if the path is gone.
+    fn apply(&self, path: &str) -> Option<Option<String>> {
+        match self.kind {
+            MatchKind::Exact => (path == self.from).then(|| self.to.clone()),
+            MatchKind::Prefix => {
+                let rest = path.strip_prefix(self.from.trim_end_matches('/'))?;
+                if !rest.is_empty() && !rest.starts_with('/') {
+                    return None;
+                }
+                let to = self.to.as_deref().map(|to| to.trim_end_matches('/'));
+                Some(to.map(|to| match (to, rest) {
+                    ("", "") => "/".to_string(),
+                    (to, rest) => format!("{}{}", to, rest),
+                }))
+            }
+            MatchKind::Pattern => {
+                let params = match_pattern(&self.from, path)?;
+                Some(self.to.as_ref().map(|to| substitute(to, &params)))
+            }
+        }
+    }
+
+    /// A path the rule applies to, used to follow the rules when checking for loops.
+    fn sample_path(&self) -> String {
+        match self.kind {
+            MatchKind::Exact => self.from.clone(),
+            MatchKind::Prefix => format!("{}/sample", self.from.trim_end_matches('/')),
+            MatchKind::Pattern => {
+                let params = param_names(&self.from)
+                    .map(|name| (name, "sample".to_string()))
+                    .collect();
+                substitute(&self.from, &params)
+            }
+        }
+    }
+
+    fn validate(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+        if !self.from.starts_with('/') {
+            errors.push(format!("`{}` does not start with /", self.from));
+        }
+        if ![301, 302, 410].contains(&self.status) {
+            errors.push(format!(
+                "`{}` has status {}, expected 301, 302 or 410",
+                self.from, self.status
+            ));
+        }
+        match (&self.to, self.status) {
+            (None, 410) => {}
+            (None, _) => errors.push(format!("`{}` has no target", self.from)),
+            (Some(_), 410) => errors.push(format!("`{}` is gone but has a target", self.from)),
+            (Some(to), _) => {
+                if self.kind == MatchKind::Pattern {
+                    let names = param_names(&self.from).collect::<Vec<_>>();
+                    for name in param_names(to) {
+                        if !names.contains(&name) {
+                            errors.push(format!("`{}` does not capture `{}`", self.from, name));
+                        }
+                    }
+                }
+            }
+        }
+        errors
+    }
+}
+
+pub struct Redirects {
+    rules: Vec<Rule>,
+}
+
+impl Redirects {
+    /// Reads and validates the rules, a missing file means no rules.
+    pub fn load(path: &str) -> Result<Redirects, Vec<String>> {
+        let content = match std::fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                return Ok(Redirects { rules: Vec::new() })
+            }
+            Err(e) => return Err(vec![format!("reading {}: {}", path, e)]),
+        };
+        let file = toml::from_str::<RedirectsFile>(&content)
+            .map_err(|e| vec![format!("parsing {}: {}", path, e)])?;
+        let errors = file
+            .rules
+            .iter()
+            .flat_map(Rule::validate)
+            .collect::<Vec<_>>();
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+        Ok(Redirects { rules: file.rules })
+    }
+
+    /// Where a request for `path` goes, trailing slashes are removed before the rules apply.
+    fn resolve(&self, path: &str) -> Option<Resolution> {
+        let normalized = match path.trim_end_matches('/') {
+            "" => "/",
+            trimmed => trimmed,
+        };
+        let rule = self
+            .rules
+            .iter()
+            .find_map(|rule| rule.apply(normalized).map(|to| (rule.status, to)));
+        match rule {
+            Some((_, None)) => Some(Resolution::Gone),
+            Some((status, Some(to))) => Some(Resolution::Redirect(
+                StatusCode::from_u16(status).unwrap_or(StatusCode::MOVED_PERMANENTLY),
+                to,
+            )),
+            None if normalized != path => Some(Resolution::Redirect(
+                StatusCode::MOVED_PERMANENTLY,
+                normalized.to_string(),
+            )),
+            None => None,
+        }
+    }
+
+    /// Follows every rule and reports the chains that come back to a path they visited.
+    pub fn find_loops(&self) -> Vec<String> {
+        let mut loops = Vec::new();
+        for rule in &self.rules {
+            let mut chain = vec![rule.sample_path()];
+            while chain.len() <= MAX_HOPS {
+                let path = chain.last().unwrap();
+                let Some(Resolution::Redirect(_, to)) = self.resolve(path) else {
+                    break;
+                };
+                if !to.starts_with('/') {
+                    break;
+                }
+                let path = to.split('?').next().unwrap_or_default().to_string();
+                let looped = chain.contains(&path);
+                chain.push(path);
+                if looped {
+                    loops.push(format!("redirect loop: {}", chain.join(" -> ")));
+                    break;
+                }
+            }
+            if chain.len() > MAX_HOPS {
+                loops.push(format!(
+                    "redirect chain from `{}` is longer than {} hops",
+                    rule.from, MAX_HOPS
+                ));
+            }
+        }
+        loops
+    }
+}
+
+/// Redirects `www.` hosts to the apex domain, removes trailing slashes and applies the rules,
+/// all in a single redirect.
+pub async fn redirect(
+    State(redirects): State<Arc<Redirects>>,
+    req: Request,
+    next: Next,
+) -> Response {
+    // HTTP/2 requests carry the host in the `:authority` of the uri instead
+    let host = req
+        .headers()
+        .get(header::HOST)
+        .and_then(|host| host.to_str().ok())
+        .or_else(|| req.uri().authority().map(|authority| authority.as_str()));
+    let apex = host.and_then(|host| host.strip_prefix("www."));
+    let resolution = redirects.resolve(req.uri().path());
+    if apex.is_none() && resolution.is_none() {
+        return next.run(req).await;
+    }
+
+    let (status, target) = match resolution {
+        Some(Resolution::Gone) => return (StatusCode::GONE, "Gone").into_response(),
+        Some(Resolution::Redirect(status, to)) => (status, to),
+        None => (StatusCode::MOVED_PERMANENTLY, req.uri().path().to_string()),
+    };
+    let mut location = match (apex, target.starts_with('/')) {
+        (Some(apex), true) => {
+            let scheme = req
+                .headers()
+                .get("x-forwarded-proto")
+                .and_then(|proto| proto.to_str().ok())
+                .unwrap_or("https");
+            format!("{}://{}{}", scheme, apex, target)
+        }
+        _ => target,
+    };
+    if let Some(query) = req.uri().query() {
+        if !location.contains('?') {
+            location = format!("{}?{}", location, query);
+        }
+    }
+    (status, [(header::LOCATION, location)]).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use axum::routing::get;
+    use axum::Router;
+    use tower::ServiceExt;
+
+    fn redirects(rules: &str) -> Redirects {
+        let file = toml::from_str::<RedirectsFile>(rules).unwrap();
+        let errors = file
+            .rules
+            .iter()
+            .flat_map(Rule::validate)
+            .collect::<Vec<_>>();
+        assert!(errors.is_empty(), "{:?}", errors);
+        Redirects { rules: file.rules }
+    }
+
+    fn redirect_to(to: &str) -> Option<Resolution> {
+        Some(Resolution::Redirect(
+            StatusCode::MOVED_PERMANENTLY,
+            to.to_string(),
+        ))
+    }
+
+    #[test]
+    fn matches_patterns() {
+        let params = match_pattern("/posts/:year/:slug", "/posts/2023/hello").unwrap();
+        assert_eq!(params["year"], "2023");
+        assert_eq!(params["slug"], "hello");
+        let params = match_pattern("/docs/*rest", "/docs/a/b/c").unwrap();
+        assert_eq!(params["rest"], "a/b/c");
+        let params = match_pattern("/docs/*rest", "/docs").unwrap();
+        assert_eq!(params["rest"], "");
+        assert!(match_pattern("/posts/:slug", "/posts/").is_none());
+        assert!(match_pattern("/posts/:slug", "/posts/a/b").is_none());
+        assert!(match_pattern("/posts/:slug", "/pages/a").is_none());
+        assert!(match_pattern("/posts/:slug", "/posts").is_none());
+    }
+
+    #[test]
+    fn resolves_rules() {
+        let redirects = redirects(
+            r#"
+            [[redirect]]
+            from = "/index"
+            to = "/"
+            [[redirect]]
+            from = "/blog"
+            to = "/posts/"
+            match = "prefix"
+            [[redirect]]
+            from = "/old/"
+            to = "/"
+            match = "prefix"
+            status = 302
+            [[redirect]]
+            from = "/p/:slug/*rest"
+            to = "/projects/:slug/*rest"
+            match = "pattern"
+            [[redirect]]
+            from = "/_next/"
+            match = "prefix"
+            status = 410
+            "#,
+        );
+        assert_eq!(redirects.resolve("/index"), redirect_to("/"));
+        assert_eq!(redirects.resolve("/index/"), redirect_to("/"));
+        assert_eq!(redirects.resolve("/blog"), redirect_to("/posts"));
+        assert_eq!(redirects.resolve("/blog/a/b"), redirect_to("/posts/a/b"));
+        assert_eq!(redirects.resolve("/blogroll"), None);
+        assert_eq!(
+            redirects.resolve("/old/a"),
+            Some(Resolution::Redirect(StatusCode::FOUND, "/a".to_string()))
+        );
+        assert_eq!(
+            redirects.resolve("/old"),
+            Some(Resolution::Redirect(StatusCode::FOUND, "/".to_string()))
+        );
+        assert_eq!(
+            redirects.resolve("/p/k8s/history"),
+            redirect_to("/projects/k8s/history")
+        );
+        assert_eq!(redirects.resolve("/_next/"), Some(Resolution::Gone));
+        assert_eq!(
+            redirects.resolve("/_next/static/a.js"),
+            Some(Resolution::Gone)
+        );
+        assert_eq!(redirects.resolve("/_nextjs"), None);
+    }
+
+    #[test]
+    fn removes_trailing_slashes() {
+        let redirects = redirects("");
+        assert_eq!(redirects.resolve("/"), None);
+        assert_eq!(redirects.resolve("/projects"), None);
+        assert_eq!(redirects.resolve("/projects/"), redirect_to("/projects"));
+        assert_eq!(redirects.resolve("/projects//"), redirect_to("/projects"));
+        assert_eq!(redirects.resolve("//"), redirect_to("/"));
+    }
+
+    #[test]
+    fn validates_rules() {
+        let file = toml::from_str::<RedirectsFile>(
+            r#"
+            [[redirect]]
+            from = "no-slash"
+            to = "/"
+            [[redirect]]
+            from = "/a"
+            [[redirect]]
+            from = "/b"
+            to = "/c"
+            status = 410
+            [[redirect]]
+            from = "/d/:slug"
+            to = "/e/:other"
+            match = "pattern"
+            status = 307
+            "#,
+        )
+        .unwrap();
+        let errors = file
+            .rules
+            .iter()
+            .flat_map(Rule::validate)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            errors,
+            [
+                "`no-slash` does not start with /",
+                "`/a` has no target",
+                "`/b` is gone but has a target",
+                "`/d/:slug` has status 307, expected 301, 302 or 410",
+                "`/d/:slug` does not capture `other`",
+            ]
+        );
+    }
+
+    #[test]
+    fn finds_loops() {
+        let redirects = redirects(
+            r#"
+            [[redirect]]
+            from = "/a"
+            to = "/b"
+            [[redirect]]
+            from = "/b"
+            to = "/a/"
+            [[redirect]]
+            from = "/c"
+            to = "/d"
+            [[redirect]]
+            from = "/old"
+            to = "/old/new"
+            match = "prefix"
+            "#,
+        );
+        let loops = redirects.find_loops();
+        assert!(loops.contains(&"redirect loop: /a -> /b -> /a/ -> /b".to_string()));
+        assert!(loops.contains(&"redirect loop: /b -> /a/ -> /b".to_string()));
+        assert!(loops
+            .iter()
+            .any(|e| e.starts_with("redirect chain from `/old`")));
+        assert_eq!(loops.len(), 3, "{:?}", loops);
+    }
+
+    fn request(host: &str, uri: &str) -> (StatusCode, Option<String>) {
+        let redirects = Arc::new(redirects(
+            r#"
+            [[redirect]]
+            from = "/index"
+            to = "/"
+            [[redirect]]
+            from = "/gone"
+            status = 410
+            "#,
+        ));
+        let app = Router::new()
+            .fallback(get(|| async { "page" }))
+            .layer(axum::middleware::from_fn_with_state(redirects, redirect));
+        let request = Request::get(uri).header(header::HOST, host);
+        let response = tokio::runtime::Runtime::new()
+            .unwrap()
+            .block_on(app.oneshot(request.body(Body::empty()).unwrap()))
+            .unwrap();
+        let location = response
+            .headers()
+            .get(header::LOCATION)
+            .map(|location| location.to_str().unwrap().to_string());
+        (response.status(), location)
+    }
+
+    #[test]
+    fn redirects_requests() {
+        let moved = |to: &str| (StatusCode::MOVED_PERMANENTLY, Some(to.to_string()));
+        assert_eq!(request("example.com", "/projects"), (StatusCode::OK, None));
+        assert_eq!(
+            request("example.com", "/projects/?a=1"),
+            moved("/projects?a=1")
+        );
+        assert_eq!(request("example.com", "/index"), moved("/"));
+        assert_eq!(
+            request("www.example.com", "/projects"),
+            moved("https://example.com/projects")
+        );
+        assert_eq!(
+            request("www.example.com", "/index/?a=1"),
+            moved("https://example.com/?a=1")
+        );
+        assert_eq!(request("example.com", "/gone"), (StatusCode::GONE, None));
+        assert_eq!(
+            request("www.example.com", "/gone/"),
+            (StatusCode::GONE, None)
+        );
+    }
+}
@@ -0,0 +1,84 @@
+use axum::{
+    body::Bytes,
+    extract::{Request, State},
+    http::{header, HeaderValue, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use leptos::config::{Env, LeptosOptions};
+use leptos::nonce::Nonce;
+use leptos::prelude::{provide_context, use_context};
+
+const REPORT_PATH: &str = "/csp-report";
+/// Length of the report bodies that are logged, reports are sent by any client.
+const MAX_REPORT_LOG: usize = 4096;
+
+const STATIC_HEADERS: &[(&str, &str)] = &[
+    (
+        "strict-transport-security",
+        "max-age=63072000; includeSubDomains",
+    ),
+    ("x-content-type-options", "nosniff"),
+    ("referrer-policy", "strict-origin-when-cross-origin"),
+    (
+        "permissions-policy",
+        "camera=(), microphone=(), geolocation=(), payment=(), usb=()",
+    ),
+    ("reporting-endpoints", "csp=\"/csp-report\""),
+];
+
+/// The policy for a response, scripts are limited to our own files and the inline hydration
+/// scripts carrying the request nonce.
+///
+/// Inline style attributes are allowed for the image placeholders, the inline critical css
+/// carries the nonce as well.
+fn content_security_policy(nonce: &Nonce, dev: bool) -> String {
+    // The dev server reloads the page over a websocket on another port
+    let connect = if dev { "'self' ws:" } else { "'self'" };
+    format!(
+        "default-src 'self'; script-src 'self' 'nonce-{nonce}' 'wasm-unsafe-eval'; \
+         style-src 'self' 'nonce-{nonce}'; style-src-attr 'unsafe-inline'; img-src 'self' data:; \
+         connect-src {connect}; object-src 'none'; base-uri 'none'; frame-ancestors 'none'; \
+         form-action 'self'; report-uri {REPORT_PATH}; report-to csp"
+    )
+}
+
+/// Adds the security headers to every response and a nonce to every request.
+///
+/// The shell picks the nonce up through [`provide_nonce`] so Leptos adds it to its inline
+/// scripts.
+pub async fn security_headers(
+    State(options): State<LeptosOptions>,
+    mut req: Request,
+    next: Next,
+) -> Response {
+    let nonce = Nonce::new();
+    req.extensions_mut().insert(nonce.clone());
+    let mut response = next.run(req).await;
+
+    let headers = response.headers_mut();
+    for (name, value) in STATIC_HEADERS {
+        headers.insert(*name, HeaderValue::from_static(value));
+    }
+    let csp = content_security_policy(&nonce, options.env == Env::DEV);
+    if let Ok(csp) = HeaderValue::from_str(&csp) {
+        headers.insert(header::CONTENT_SECURITY_POLICY, csp);
+    }
+    response
+}
+
+/// Provides the nonce of the current request to the Leptos context, call it in the shell.
+pub fn provide_nonce() {
+    if let Some(nonce) = use_context::<http::request::Parts>()
+        .and_then(|parts| parts.extensions.get::<Nonce>().cloned())
+    {
+        provide_context(nonce);
+    }
+}
+
+/// Logs the violation reports sent by browsers.
+pub async fn csp_report(body: Bytes) -> Response {
+    let report = String::from_utf8_lossy(&body[..body.len().min(MAX_REPORT_LOG)]);
+    tracing::warn!(report = %report, "content security policy violation");
+    StatusCode::NO_CONTENT.into_response()
+}
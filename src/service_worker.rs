@@ -0,0 +1,124 @@
+use crate::caching::{content_etag, etag_matches};
+use crate::collections::{ssr::read_entries, Collection};
+use crate::pages::ssr::read_pages;
+use axum::{
+    extract::State,
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+};
+use leptos::config::LeptosOptions;
+use sha2::{Digest, Sha256};
+use std::path::PathBuf;
+use std::sync::{Arc, LazyLock, Mutex};
+
+const TEMPLATE: &str = include_str!("sw.js");
+const MANIFEST: &str = "public/manifest.json";
+
+/// Files hashed into the worker version, a change to any of them installs a new worker.
+async fn versioned_files(options: &LeptosOptions) -> Vec<PathBuf> {
+    let mut files = vec![PathBuf::from(MANIFEST)];
+    let pkg = PathBuf::from(options.site_root.as_ref()).join(options.site_pkg_dir.as_ref());
+    if let Ok(mut dir) = tokio::fs::read_dir(&pkg).await {
+        while let Ok(Some(entry)) = dir.next_entry().await {
+            files.push(entry.path());
+        }
+    }
+    for collection in Collection::ALL {
+        let config = collection.config();
+        if let Ok(mut dir) = tokio::fs::read_dir(config.dir).await {
+            while let Ok(Some(entry)) = dir.next_entry().await {
+                files.push(entry.path());
+            }
+        }
+    }
+    files.sort();
+    files
+}
+
+/// Urls precached on install: the pages, the build output and the manifest icons.
+async fn precache_urls(options: &LeptosOptions) -> Vec<String> {
+    let mut urls = vec!["/".to_string(), "/offline".to_string()];
+    for collection in Collection::ALL {
+        urls.push(format!("/{}", collection.config().route_prefix));
+        urls.extend(read_entries(collection).await.into_iter().map(|e| e.url));
+    }
+    urls.extend(read_pages().await.into_iter().map(|p| p.url));
+
+    let pkg = PathBuf::from(options.site_root.as_ref()).join(options.site_pkg_dir.as_ref());
+    if let Ok(mut dir) = tokio::fs::read_dir(&pkg).await {
+        while let Ok(Some(entry)) = dir.next_entry().await {
+            if let Ok(name) = entry.file_name().into_string() {
+                urls.push(format!("/{}/{}", options.site_pkg_dir, name));
+            }
+        }
+    }
+
+    urls.push("/manifest.json".to_string());
+    let icons = tokio::fs::read_to_string(MANIFEST)
+        .await
+        .ok()
+        .and_then(|m| serde_json::from_str::<serde_json::Value>(&m).ok())
+        .and_then(|m| m["icons"].as_array().cloned())
+        .unwrap_or_default();
+    urls.extend(
+        icons
+            .iter()
+            .filter_map(|icon| icon["src"].as_str().map(str::to_string)),
+    );
+    urls
+}
+
+/// The service worker built for the content and build with the ETag `etag`.
+struct Script {
+    etag: String,
+    body: String,
+}
+
+static SCRIPT: LazyLock<Mutex<Option<Arc<Script>>>> = LazyLock::new(Default::default);
+
+async fn build(options: &LeptosOptions, etag: String) -> Script {
+    let urls = precache_urls(options).await;
+    let mut hasher = Sha256::new();
+    for url in &urls {
+        hasher.update(url);
+    }
+    for file in versioned_files(options).await {
+        if let Ok(content) = tokio::fs::read(&file).await {
+            hasher.update(content);
+        }
+    }
+    let version = format!("{:x}", hasher.finalize());
+
+    let body = TEMPLATE
+        .replace("__VERSION__", &version[..16])
+        .replace("__PRECACHE__", &serde_json::to_string(&urls).unwrap());
+    Script { etag, body }
+}
+
+/// Serves the service worker with the precache list and version filled in. It is only built
+/// again when the content or the build changes.
+pub async fn service_worker(State(options): State<LeptosOptions>, headers: HeaderMap) -> Response {
+    let etag = content_etag().await;
+    let cached = SCRIPT.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    let script = match cached.filter(|script| script.etag == etag) {
+        Some(script) => script,
+        None => {
+            let script = Arc::new(build(&options, etag).await);
+            *SCRIPT.lock().unwrap_or_else(|e| e.into_inner()) = Some(script.clone());
+            script
+        }
+    };
+    let cache_headers = [
+        (header::ETAG, script.etag.clone()),
+        (header::CACHE_CONTROL, "no-cache".to_string()),
+    ];
+    if etag_matches(&headers, &script.etag) {
+        return (StatusCode::NOT_MODIFIED, cache_headers).into_response();
+    }
+    (
+        cache_headers,
+        [(header::CONTENT_TYPE, "application/javascript")],
+        script.body.clone(),
+    )
+        .into_response()
+}
@@ -0,0 +1,65 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+/// Seconds in-flight requests get to finish after a shutdown signal.
+const DRAIN_TIMEOUT_ENV: &str = "SHUTDOWN_DRAIN_TIMEOUT";
+const DEFAULT_DRAIN_TIMEOUT: Duration = Duration::from_secs(30);
+/// Seconds `/readyz` reports unready before the listener stops, for load balancers to notice.
+const UNREADY_DELAY_ENV: &str = "SHUTDOWN_UNREADY_DELAY";
+const DEFAULT_UNREADY_DELAY: Duration = Duration::from_secs(5);
+
+static SHUTTING_DOWN: AtomicBool = AtomicBool::new(false);
+
+/// Whether a shutdown signal has been received, `/readyz` reports unready from then on.
+pub fn is_shutting_down() -> bool {
+    SHUTTING_DOWN.load(Ordering::Relaxed)
+}
+
+fn seconds(env: &str, default: Duration) -> Duration {
+    std::env::var(env)
+        .ok()
+        .and_then(|secs| secs.parse().ok())
+        .map(Duration::from_secs)
+        .unwrap_or(default)
+}
+
+pub fn drain_timeout() -> Duration {
+    seconds(DRAIN_TIMEOUT_ENV, DEFAULT_DRAIN_TIMEOUT)
+}
+
+/// Resolves on SIGINT or SIGTERM, after marking the server as shutting down and waiting the
+/// unready delay while it keeps serving. A second SIGINT skips the delay.
+pub async fn signal() {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("failed to install the SIGINT handler");
+    };
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("failed to install the SIGTERM handler")
+            .recv()
+            .await;
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => tracing::info!("received SIGINT, shutting down"),
+        _ = terminate => tracing::info!("received SIGTERM, shutting down"),
+    }
+    SHUTTING_DOWN.store(true, Ordering::Relaxed);
+
+    let delay = seconds(UNREADY_DELAY_ENV, DEFAULT_UNREADY_DELAY);
+    if !delay.is_zero() {
+        tracing::info!(
+            "reporting unready for {:?} before closing the listener",
+            delay
+        );
+        tokio::select! {
+            _ = tokio::time::sleep(delay) => {}
+            _ = tokio::signal::ctrl_c() => tracing::info!("received SIGINT again, closing now"),
+        }
+    }
+}
@@ -0,0 +1,41 @@
+use crate::collections::{ssr::read_entries, Collection};
+use crate::pages::ssr::read_pages;
+use crate::SITE_URL;
+use axum::{
+    http::header,
+    response::{IntoResponse, Response},
+};
+
+fn url(path: &str, lastmod: Option<String>) -> String {
+    match lastmod {
+        Some(lastmod) => format!(
+            "<url><loc>{}{}</loc><lastmod>{}</lastmod></url>",
+            SITE_URL, path, lastmod
+        ),
+        None => format!("<url><loc>{}{}</loc></url>", SITE_URL, path),
+    }
+}
+
+/// Lists the home page, every collection listing and entry and the markdown pages.
+pub async fn sitemap() -> Response {
+    let mut urls = vec![url("/", None)];
+    for collection in Collection::ALL {
+        urls.push(url(&format!("/{}", collection.config().route_prefix), None));
+        for entry in read_entries(collection).await {
+            urls.push(url(&entry.url, Some(entry.updated.unwrap_or(entry.date).to_string())));
+        }
+    }
+    for page in read_pages().await {
+        urls.push(url(&page.url, None));
+    }
+
+    let body = format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?><urlset xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">{}</urlset>"#,
+        urls.concat()
+    );
+    (
+        [(header::CONTENT_TYPE, "application/xml; charset=utf-8")],
+        body,
+    )
+        .into_response()
+}
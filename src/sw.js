@@ -0,0 +1,77 @@
+// Generated by the server at /sw.js, the placeholders are filled in by `service_worker.rs`.
+const VERSION = "__VERSION__";
+const PRECACHE = __PRECACHE__;
+const CACHE = `website-${VERSION}`;
+const OFFLINE_URL = "/offline";
+// Served with a name that changes with the content, or versioned together with this worker
+const CACHE_FIRST = ["/pkg/", "/images/", "/og/"];
+// Edited in place, so only served from the cache when offline
+const NETWORK_FIRST = ["/assets/"];
+
+self.addEventListener("install", (event) => {
+  event.waitUntil(
+    caches
+      .open(CACHE)
+      .then((cache) => cache.addAll(PRECACHE))
+      .then(() => self.skipWaiting()),
+  );
+});
+
+self.addEventListener("activate", (event) => {
+  event.waitUntil(
+    caches
+      .keys()
+      .then((keys) =>
+        Promise.all(
+          keys.filter((key) => key !== CACHE).map((key) => caches.delete(key)),
+        ),
+      )
+      .then(() => self.clients.claim()),
+  );
+});
+
+async function networkFirst(request) {
+  const cache = await caches.open(CACHE);
+  try {
+    const response = await fetch(request);
+    if (response.ok) {
+      cache.put(request, response.clone());
+    }
+    return response;
+  } catch (error) {
+    return (
+      (await cache.match(request, { ignoreSearch: true })) ||
+      (request.mode === "navigate" && (await cache.match(OFFLINE_URL))) ||
+      Response.error()
+    );
+  }
+}
+
+async function cacheFirst(request) {
+  const cache = await caches.open(CACHE);
+  const cached = await cache.match(request);
+  if (cached) {
+    return cached;
+  }
+  const response = await fetch(request);
+  if (response.ok) {
+    cache.put(request, response.clone());
+  }
+  return response;
+}
+
+self.addEventListener("fetch", (event) => {
+  const request = event.request;
+  const url = new URL(request.url);
+  if (request.method !== "GET" || url.origin !== self.location.origin) {
+    return;
+  }
+  if (
+    request.mode === "navigate" ||
+    NETWORK_FIRST.some((prefix) => url.pathname.startsWith(prefix))
+  ) {
+    event.respondWith(networkFirst(request));
+  } else if (CACHE_FIRST.some((prefix) => url.pathname.startsWith(prefix))) {
+    event.respondWith(cacheFirst(request));
+  }
+});
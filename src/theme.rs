@@ -0,0 +1,224 @@
+use leptos::prelude::*;
+
+/// Name of the cookie the theme preference is stored in.
+pub const COOKIE: &str = "theme";
+
+/// The color theme, `System` follows `prefers-color-scheme`.
+///
+/// It is set as `data-theme` on `<html>`, the `dark:` variant in `input.css` is keyed on it.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Theme {
+    System,
+    Light,
+    Dark,
+}
+
+impl Theme {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Theme::System => "system",
+            Theme::Light => "light",
+            Theme::Dark => "dark",
+        }
+    }
+
+    pub fn parse(value: &str) -> Theme {
+        match value {
+            "light" => Theme::Light,
+            "dark" => Theme::Dark,
+            _ => Theme::System,
+        }
+    }
+
+    /// The browser ui color, `System` has no single color and falls back to the dark one.
+    pub fn theme_color(self) -> &'static str {
+        match self {
+            Theme::Light => "#e2e8f0",
+            Theme::Dark | Theme::System => "#314158",
+        }
+    }
+
+    pub fn background_color(self) -> &'static str {
+        match self {
+            Theme::Light => "#f9fafb",
+            Theme::Dark | Theme::System => "#101828",
+        }
+    }
+
+    fn next(self) -> Theme {
+        match self {
+            Theme::System => Theme::Light,
+            Theme::Light => Theme::Dark,
+            Theme::Dark => Theme::System,
+        }
+    }
+}
+
+/// Reads the theme from a `Cookie` header value.
+pub fn from_cookie_header(header: &str) -> Theme {
+    header
+        .split(';')
+        .filter_map(|c| c.trim().split_once('='))
+        .find(|(name, _)| *name == COOKIE)
+        .map(|(_, value)| Theme::parse(value))
+        .unwrap_or(Theme::System)
+}
+
+/// The theme of the current request during SSR, or of the document once hydrated.
+pub fn current_theme() -> Theme {
+    #[cfg(feature = "ssr")]
+    {
+        use_context::<http::request::Parts>()
+            .and_then(|parts| {
+                parts
+                    .headers
+                    .get(http::header::COOKIE)?
+                    .to_str()
+                    .ok()
+                    .map(from_cookie_header)
+            })
+            .unwrap_or(Theme::System)
+    }
+    #[cfg(not(feature = "ssr"))]
+    {
+        document()
+            .document_element()
+            .and_then(|html| html.get_attribute("data-theme"))
+            .map(|theme| Theme::parse(&theme))
+            .unwrap_or(Theme::System)
+    }
+}
+
+/// The `theme-color` meta tags, one per color scheme so `System` can follow the media query.
+#[component]
+pub fn ThemeColor(theme: Theme) -> impl IntoView {
+    let color = |fallback: Theme| match theme {
+        Theme::System => fallback.theme_color(),
+        theme => theme.theme_color(),
+    };
+    // `media` is not a known attribute of <meta> in the view macro
+    let meta = |scheme: Theme| {
+        leptos::html::meta()
+            .name("theme-color")
+            .id(format!("theme-color-{}", scheme.as_str()))
+            .content(color(scheme))
+            .attr("media", format!("(prefers-color-scheme: {})", scheme.as_str()))
+    };
+    view! {
+        {meta(Theme::Light)}
+        {meta(Theme::Dark)}
+    }
+}
+
+#[cfg(not(feature = "ssr"))]
+fn apply(theme: Theme) {
+    use wasm_bindgen::JsCast;
+    let document = document();
+    if let Some(html) = document.document_element() {
+        let _ = html.set_attribute("data-theme", theme.as_str());
+    }
+    for (id, fallback) in [
+        ("theme-color-light", Theme::Light),
+        ("theme-color-dark", Theme::Dark),
+    ] {
+        if let Some(meta) = document.get_element_by_id(id) {
+            let color = match theme {
+                Theme::System => fallback.theme_color(),
+                theme => theme.theme_color(),
+            };
+            let _ = meta.set_attribute("content", color);
+        }
+    }
+    let cookie = match theme {
+        Theme::System => format!("{}=; path=/; max-age=0; samesite=lax", COOKIE),
+        theme => format!(
+            "{}={}; path=/; max-age=31536000; samesite=lax",
+            COOKIE,
+            theme.as_str()
+        ),
+    };
+    let _ = document
+        .unchecked_into::<web_sys::HtmlDocument>()
+        .set_cookie(&cookie);
+}
+
+#[cfg(feature = "ssr")]
+fn apply(_theme: Theme) {}
+
+/// Cycles the theme between system, light and dark.
+#[island]
+pub fn ThemeToggle() -> impl IntoView {
+    let (theme, set_theme) = signal(current_theme());
+    let label = move || match theme.get() {
+        Theme::System => "Theme: system, switch to light",
+        Theme::Light => "Theme: light, switch to dark",
+        Theme::Dark => "Theme: dark, switch to system",
+    };
+
+    view! {
+        <button
+            type="button"
+            class="flex items-center m-4 text-gray-600 dark:text-gray-300 hover:text-gray-900 dark:hover:text-gray-100 cursor-pointer"
+            aria-label=label
+            title=label
+            on:click=move |_| {
+                let next = theme.get_untracked().next();
+                apply(next);
+                set_theme.set(next);
+            }
+        >
+            <svg
+                class="w-6 h-6"
+                xmlns="http://www.w3.org/2000/svg"
+                viewBox="0 0 24 24"
+                fill="none"
+                stroke="currentColor"
+                stroke-width="2"
+                aria-hidden="true"
+            >
+                {move || match theme.get() {
+                    Theme::System => view! { <path d="M3 5h18v11H3zM8 20h8M12 16v4"></path> }.into_any(),
+                    Theme::Light => view! {
+                        <path d="M12 3v2M12 19v2M5.6 5.6l1.4 1.4M17 17l1.4 1.4M3 12h2M19 12h2M5.6 18.4L7 17M17 7l1.4-1.4M12 8a4 4 0 100 8 4 4 0 000-8z"></path>
+                    }.into_any(),
+                    Theme::Dark => view! { <path d="M21 12.8A9 9 0 1111.2 3a7 7 0 009.8 9.8z"></path> }.into_any(),
+                }}
+            </svg>
+        </button>
+    }
+}
+
+#[cfg(feature = "ssr")]
+pub mod ssr {
+    use super::{from_cookie_header, Theme};
+    use axum::{
+        http::{header, HeaderMap, StatusCode},
+        response::{IntoResponse, Response},
+    };
+
+    /// Serves `public/manifest.json` with the colors of the requester's theme.
+    pub async fn manifest(headers: HeaderMap) -> Response {
+        let theme = headers
+            .get(header::COOKIE)
+            .and_then(|c| c.to_str().ok())
+            .map(from_cookie_header)
+            .unwrap_or(Theme::System);
+        let Ok(mut manifest) = tokio::fs::read_to_string("public/manifest.json")
+            .await
+            .map_err(|_| ())
+            .and_then(|m| serde_json::from_str::<serde_json::Value>(&m).map_err(|_| ()))
+        else {
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        };
+        manifest["theme_color"] = theme.theme_color().into();
+        manifest["background_color"] = theme.background_color().into();
+        (
+            [
+                (header::CONTENT_TYPE, "application/manifest+json"),
+                (header::VARY, "Cookie"),
+            ],
+            manifest.to_string(),
+        )
+            .into_response()
+    }
+}
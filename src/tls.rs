@@ -0,0 +1,191 @@
+use axum::{
+    http::{header, HeaderMap, StatusCode, Uri},
+    response::{IntoResponse, Response},
+    serve::Listener,
+    Router,
+};
+use std::io;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, SystemTime};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+use tokio_rustls::rustls::{
+    self,
+    pki_types::{pem::PemObject, CertificateDer, PrivateKeyDer},
+    ServerConfig,
+};
+use tokio_rustls::{server::TlsStream, TlsAcceptor};
+
+/// Paths of the PEM encoded certificate chain and private key, HTTPS is enabled when both are set.
+const CERT_ENV: &str = "TLS_CERT";
+const KEY_ENV: &str = "TLS_KEY";
+/// Address of the optional plain HTTP listener redirecting to HTTPS.
+const REDIRECT_ADDR_ENV: &str = "HTTP_REDIRECT_ADDR";
+const RELOAD_INTERVAL: Duration = Duration::from_secs(10);
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+/// Handshaken connections waiting for the server to accept them.
+const ACCEPT_BACKLOG: usize = 64;
+
+pub struct TlsSettings {
+    cert: PathBuf,
+    key: PathBuf,
+    /// Plain HTTP address redirecting to HTTPS, if any.
+    pub redirect_addr: Option<SocketAddr>,
+}
+
+impl TlsSettings {
+    pub fn from_env() -> Option<TlsSettings> {
+        let cert = std::env::var_os(CERT_ENV)?;
+        let key = std::env::var_os(KEY_ENV)?;
+        let redirect_addr = std::env::var(REDIRECT_ADDR_ENV)
+            .ok()
+            .map(|addr| addr.parse().expect("invalid HTTP_REDIRECT_ADDR"));
+        Some(TlsSettings {
+            cert: cert.into(),
+            key: key.into(),
+            redirect_addr,
+        })
+    }
+
+    fn load(&self) -> Result<Arc<ServerConfig>, String> {
+        let certs = CertificateDer::pem_file_iter(&self.cert)
+            .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+            .map_err(|e| format!("reading {}: {}", self.cert.display(), e))?;
+        let key = PrivateKeyDer::from_pem_file(&self.key)
+            .map_err(|e| format!("reading {}: {}", self.key.display(), e))?;
+        let mut config =
+            ServerConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+                .with_safe_default_protocol_versions()
+                .and_then(|builder| builder.with_no_client_auth().with_single_cert(certs, key))
+                .map_err(|e| e.to_string())?;
+        config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+        Ok(Arc::new(config))
+    }
+
+    fn modified(&self) -> Option<(SystemTime, SystemTime)> {
+        let modified = |path: &PathBuf| std::fs::metadata(path).and_then(|m| m.modified()).ok();
+        Some((modified(&self.cert)?, modified(&self.key)?))
+    }
+}
+
+/// Reloads the certificate when the files change, a broken pair keeps the previous one.
+async fn watch(settings: TlsSettings, config: Arc<RwLock<Arc<ServerConfig>>>) {
+    let mut last = settings.modified();
+    loop {
+        tokio::time::sleep(RELOAD_INTERVAL).await;
+        let modified = settings.modified();
+        if modified.is_none() || modified == last {
+            continue;
+        }
+        last = modified;
+        match settings.load() {
+            Ok(new) => {
+                *config.write().unwrap_or_else(|e| e.into_inner()) = new;
+                tracing::info!("reloaded the TLS certificate");
+            }
+            Err(e) => tracing::error!("failed to reload the TLS certificate: {}", e),
+        }
+    }
+}
+
+/// A listener yielding connections once their TLS handshake is done.
+///
+/// Handshakes run in their own tasks so a slow client does not hold up the others.
+pub struct TlsListener {
+    incoming: mpsc::Receiver<(TlsStream<TcpStream>, SocketAddr)>,
+    local_addr: SocketAddr,
+}
+
+impl TlsListener {
+    /// Loads the certificate, failing if it is invalid, and starts watching it for changes.
+    pub async fn bind(addr: SocketAddr, settings: TlsSettings) -> io::Result<TlsListener> {
+        let config = settings.load().map_err(io::Error::other)?;
+        let config = Arc::new(RwLock::new(config));
+        tokio::spawn(watch(settings, config.clone()));
+
+        let listener = TcpListener::bind(addr).await?;
+        let local_addr = listener.local_addr()?;
+        let (sender, incoming) = mpsc::channel(ACCEPT_BACKLOG);
+        tokio::spawn(async move {
+            loop {
+                let (stream, remote) = match listener.accept().await {
+                    Ok(connection) => connection,
+                    Err(e) => {
+                        tracing::error!("failed to accept a connection: {}", e);
+                        tokio::time::sleep(Duration::from_secs(1)).await;
+                        continue;
+                    }
+                };
+                if sender.is_closed() {
+                    break;
+                }
+                let acceptor =
+                    TlsAcceptor::from(config.read().unwrap_or_else(|e| e.into_inner()).clone());
+                let sender = sender.clone();
+                tokio::spawn(async move {
+                    match tokio::time::timeout(HANDSHAKE_TIMEOUT, acceptor.accept(stream)).await {
+                        Ok(Ok(stream)) => {
+                            let _ = sender.send((stream, remote)).await;
+                        }
+                        Ok(Err(e)) => {
+                            tracing::debug!("TLS handshake with {} failed: {}", remote, e)
+                        }
+                        Err(_) => tracing::debug!("TLS handshake with {} timed out", remote),
+                    }
+                });
+            }
+        });
+
+        Ok(TlsListener {
+            incoming,
+            local_addr,
+        })
+    }
+}
+
+impl Listener for TlsListener {
+    type Io = TlsStream<TcpStream>;
+    type Addr = SocketAddr;
+
+    async fn accept(&mut self) -> (Self::Io, Self::Addr) {
+        match self.incoming.recv().await {
+            Some(connection) => connection,
+            // The accept loop only stops once this listener is dropped
+            None => std::future::pending().await,
+        }
+    }
+
+    fn local_addr(&self) -> io::Result<Self::Addr> {
+        Ok(self.local_addr)
+    }
+}
+
+/// Redirects every request to the same path on HTTPS, `https_port` is added unless it is 443.
+pub fn redirect_router(https_port: u16) -> Router {
+    Router::new().fallback(move |headers: HeaderMap, uri: Uri| async move {
+        redirect(https_port, &headers, &uri)
+    })
+}
+
+fn redirect(https_port: u16, headers: &HeaderMap, uri: &Uri) -> Response {
+    let Some(host) = headers
+        .get(header::HOST)
+        .and_then(|host| host.to_str().ok())
+        .and_then(|host| host.parse::<axum::http::uri::Authority>().ok())
+    else {
+        return StatusCode::BAD_REQUEST.into_response();
+    };
+    let port = match https_port {
+        443 => String::new(),
+        port => format!(":{}", port),
+    };
+    let path = uri.path_and_query().map(|p| p.as_str()).unwrap_or("/");
+    let location = format!("https://{}{}{}", host.host(), port, path);
+    (
+        StatusCode::MOVED_PERMANENTLY,
+        [(header::LOCATION, location)],
+    )
+        .into_response()
+}